@@ -0,0 +1,306 @@
+// Copyright (C) 2025  Mátyás Mustoha
+
+use crate::{
+    Error,
+    cli::{
+        CliExportOutput, CliExportTarget, CliFileFormat, CliFormatOptions, CliImportChecks,
+        CliInjectInput, CliRowChanges,
+    },
+    export, import, path_to_tablename, read_nxd_rows, workbook,
+};
+use ffttic_nxdtext_core as nxd;
+use std::{
+    fs::{self, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+
+/// The outcome of processing a directory or an archive, file by file.
+#[derive(Default)]
+pub struct Summary {
+    pub done: usize,
+    pub skipped: Vec<(PathBuf, String)>,
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+impl Summary {
+    pub fn add_result(&mut self, path: &Path, result: Result<(), Error>) {
+        match result {
+            Ok(()) => self.done += 1,
+            Err(err) => self.failed.push((path.to_path_buf(), err.0)),
+        }
+    }
+
+    /// Prints the results, and fails if any of the files did.
+    pub fn finish(self, action: &str) -> Result<(), Error> {
+        println!("{} {} table(s)", action, self.done);
+        if !self.skipped.is_empty() {
+            println!("Skipped {} file(s):", self.skipped.len());
+            for (path, reason) in &self.skipped {
+                println!("  {}: {}", path.display(), reason);
+            }
+        }
+        if !self.failed.is_empty() {
+            println!("Failed {} file(s):", self.failed.len());
+            for (path, reason) in &self.failed {
+                println!("  {}: {}", path.display(), reason.replace('\n', "\n  "));
+            }
+            return Err(Error(format!(
+                "{} file(s) could not be processed",
+                self.failed.len()
+            )));
+        }
+        Ok(())
+    }
+}
+
+
+pub fn is_nxd_path(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("nxd"))
+}
+
+
+/// Lists every NXD file under a directory, relative to it.
+fn find_nxd_files(root: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if is_nxd_path(&path) {
+                files.push(path.strip_prefix(root)?.to_path_buf());
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+
+/// Returns why a file can't be processed, if it can't.
+pub fn skip_reason(nxd_path: &Path, schemas: &nxd::Schemas) -> Option<String> {
+    match path_to_tablename(nxd_path) {
+        Ok(tablename) if schemas.get(tablename).is_some() => None,
+        Ok(tablename) => Some(format!("No layout for the table `{}`", tablename)),
+        Err(err) => Some(err.0),
+    }
+}
+
+
+fn has_sets(nxd_path: &Path) -> Result<bool, Error> {
+    let nxdfile = File::open(nxd_path)?;
+    let set_infos = nxd::read_set_infos(&mut BufReader::new(nxdfile))?;
+    Ok(!set_infos.is_empty())
+}
+
+
+/// Exports every table into a single workbook, with a sheet for each.
+fn export_workbook(
+    nxd_dir: &Path,
+    schemas: &nxd::Schemas,
+    out_path: &Path,
+    format: CliFileFormat,
+) -> Result<(), Error> {
+    let mut summary = Summary::default();
+    let mut tables: Vec<(String, Vec<(String, String)>)> = Vec::new();
+
+    for rel_path in find_nxd_files(nxd_dir)? {
+        let nxd_path = nxd_dir.join(&rel_path);
+        if let Some(reason) = skip_reason(&nxd_path, schemas) {
+            summary.skipped.push((rel_path, reason));
+            continue;
+        }
+        let tablename = path_to_tablename(&nxd_path)?.to_owned();
+        if tables.iter().any(|(name, _)| *name == tablename) {
+            let reason = format!("The table `{}` already has a sheet", tablename);
+            summary.skipped.push((rel_path, reason));
+            continue;
+        }
+
+        match read_nxd_rows(&nxd_path, schemas) {
+            Ok(rows) => {
+                summary.done += 1;
+                tables.push((tablename, rows));
+            },
+            Err(err) => summary.failed.push((rel_path, err.0)),
+        }
+    }
+
+    let sheets = tables
+        .iter()
+        .map(|(tablename, rows)| workbook::Sheet {
+            tablename,
+            rows,
+            target_texts: None,
+        })
+        .collect::<Vec<_>>();
+    if let Some(parent) = out_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    match format {
+        CliFileFormat::Ods => workbook::save_ods(&sheets, out_path)?,
+        _ => workbook::save_xlsx(&sheets, out_path)?,
+    }
+
+    summary.finish("Exported")
+}
+
+
+pub fn export_all(
+    nxd_dir: &Path,
+    schemas: &nxd::Schemas,
+    out_dir: &Path,
+    format: CliFileFormat,
+    format_options: &CliFormatOptions,
+    group_by_set: bool,
+    all_columns: bool,
+) -> Result<(), Error> {
+    if (group_by_set || all_columns) && !matches!(format, CliFileFormat::Json | CliFileFormat::Po) {
+        return Err(Error(
+            "Only JSON and PO exports can be grouped by set or have all the columns".to_owned(),
+        ));
+    }
+    if matches!(format, CliFileFormat::Xlsx | CliFileFormat::Ods) {
+        return export_workbook(nxd_dir, schemas, out_dir, format);
+    }
+
+    let mut summary = Summary::default();
+
+    for rel_path in find_nxd_files(nxd_dir)? {
+        let nxd_path = nxd_dir.join(&rel_path);
+        if let Some(reason) = skip_reason(&nxd_path, schemas) {
+            summary.skipped.push((rel_path, reason));
+            continue;
+        }
+
+        let out_path = Some(out_dir.join(&rel_path).with_extension(format.extension()));
+        let mut output = CliExportOutput::default();
+        match format {
+            CliFileFormat::Json => output.out_json = out_path,
+            CliFileFormat::Po => output.out_po = out_path,
+            CliFileFormat::Xliff => output.out_xliff = out_path,
+            CliFileFormat::Csv => output.out_csv = out_path,
+            CliFileFormat::Tsv => output.out_tsv = out_path,
+            CliFileFormat::Xlsx => output.out_xlsx = out_path,
+            CliFileFormat::Ods => output.out_ods = out_path,
+        }
+        // Tables with a single key have no sets, and are exported as they are
+        let result = has_sets(&nxd_path).and_then(|has_sets| {
+            export::run(
+                &nxd_path,
+                schemas,
+                &output,
+                format_options,
+                &CliExportTarget::default(),
+                group_by_set && has_sets,
+                all_columns,
+            )
+        });
+        summary.add_result(&rel_path, result);
+    }
+
+    summary.finish("Exported")
+}
+
+
+/// Finds the translation files of a table, next to each other in the input directory.
+fn find_inputs(translation_path: &Path) -> CliInjectInput {
+    let find_input = |format: CliFileFormat| {
+        Some(translation_path.with_extension(format.extension())).filter(|path| path.is_file())
+    };
+    CliInjectInput {
+        json: find_input(CliFileFormat::Json),
+        po: find_input(CliFileFormat::Po),
+        xliff: find_input(CliFileFormat::Xliff),
+        csv: find_input(CliFileFormat::Csv),
+        tsv: find_input(CliFileFormat::Tsv),
+        xlsx: find_input(CliFileFormat::Xlsx),
+        ods: find_input(CliFileFormat::Ods),
+        layer: Vec::new(),
+    }
+}
+
+
+/// Imports the translations of a directory, or of a workbook with a sheet for every table,
+/// into the matching NXD files. Files without a translation or a layout are copied as they
+/// are, so the output is complete.
+pub fn import_all(
+    nxd_dir: &Path,
+    schemas: &nxd::Schemas,
+    input_path: &Path,
+    out_dir: &Path,
+    layout: nxd::TextLayout,
+) -> Result<(), Error> {
+    let mut summary = Summary::default();
+
+    if !input_path.exists() {
+        return Err(Error(format!("Input not found: {}", input_path.display())));
+    }
+    let workbook_sheets = match input_path.is_file() {
+        true => Some(workbook::sheet_names(input_path)?),
+        false => None,
+    };
+    let is_ods = input_path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("ods"));
+
+    for rel_path in find_nxd_files(nxd_dir)? {
+        let nxd_path = nxd_dir.join(&rel_path);
+        let out_nxd = out_dir.join(&rel_path);
+
+        let input = match &workbook_sheets {
+            Some(sheet_names) => {
+                let has_sheet = path_to_tablename(&nxd_path)
+                    .is_ok_and(|tablename| sheet_names.iter().any(|name| name == tablename));
+                let workbook_path = Some(input_path.to_path_buf()).filter(|_| has_sheet);
+                match is_ods {
+                    true => CliInjectInput {
+                        ods: workbook_path,
+                        ..Default::default()
+                    },
+                    false => CliInjectInput {
+                        xlsx: workbook_path,
+                        ..Default::default()
+                    },
+                }
+            },
+            None => find_inputs(&input_path.join(&rel_path)),
+        };
+        let reason = match skip_reason(&nxd_path, schemas) {
+            None if input.files().next().is_none() => Some("No translation".to_owned()),
+            reason => reason,
+        };
+
+        if let Some(reason) = reason {
+            let copy_result = out_nxd
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| fs::copy(&nxd_path, &out_nxd));
+            match copy_result {
+                Ok(_) => summary
+                    .skipped
+                    .push((rel_path, format!("{}, copied as is", reason))),
+                Err(err) => summary.failed.push((rel_path, err.to_string())),
+            }
+            continue;
+        }
+
+        let result = import::run(
+            &nxd_path,
+            schemas,
+            &input,
+            &out_nxd,
+            &CliRowChanges::default(),
+            layout,
+            &CliImportChecks::default(),
+        );
+        summary.add_result(&rel_path, result);
+    }
+
+    summary.finish("Imported")
+}
@@ -1,7 +1,7 @@
 // Copyright (C) 2025  Mátyás Mustoha
 
-use clap::{Args, Parser, Subcommand};
-use std::path::PathBuf;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::path::{Path, PathBuf};
 
 
 const HELP_TEMPLATE: &str = "\
@@ -18,33 +18,191 @@ const HELP_TEMPLATE: &str = "\
 pub struct Cli {
     #[command(subcommand)]
     pub command: CliCommand,
+
+    /// Load table layouts from a TOML or JSON file, or from a directory of such files
+    #[arg(long, value_name = "PATH", global = true)]
+    pub schema: Vec<PathBuf>,
+
+    /// Don't use the built-in table layouts, only the ones given with `--schema`
+    #[arg(long, global = true)]
+    pub no_builtin_schemas: bool,
 }
 
 #[derive(Subcommand, Debug)]
 pub enum CliCommand {
     /// Export text from NXD files.
     Export {
-        /// The source NXD file
+        /// The source NXD file, or a .pac archive to export every uncompressed table of
         nxd: PathBuf,
 
         #[command(flatten)]
         output: CliExportOutput,
+
+        #[command(flatten)]
+        format_options: CliFormatOptions,
+
+        #[command(flatten)]
+        target: CliExportTarget,
+
+        /// Group the entries by the sets of a table with two keys
+        #[arg(long)]
+        group_by_set: bool,
+
+        /// Also export the numbers and flags of every row to the JSON output
+        #[arg(long, conflicts_with = "group_by_set")]
+        all_columns: bool,
     },
-    /// Import text from either a JSON or a PO file.
+    /// Import text from a JSON, PO, XLIFF, CSV, TSV, XLSX or ODS file, or from several
+    /// layered on top of each other.
     Import {
-        /// The source NXD file
+        /// The source NXD file, or a .pac archive to import into every uncompressed table of
         nxd: PathBuf,
 
         #[command(flatten)]
         input: CliInjectInput,
 
+        /// The output NXD file, or the repacked archive
+        #[arg(short, long, value_name = "FILE", required = true)]
+        out: PathBuf,
+
+        #[command(flatten)]
+        row_changes: CliRowChanges,
+
+        /// How to place the texts in the output file
+        #[arg(
+            long,
+            value_enum,
+            default_value_t = CliTextLayout::Rebuild,
+            conflicts_with_all = ["add_rows", "remove_row"]
+        )]
+        layout: CliTextLayout,
+
+        #[command(flatten)]
+        checks: CliImportChecks,
+    },
+    /// Export text from every NXD file in a directory, including its subdirectories.
+    ExportAll {
+        /// The directory of the source NXD files
+        nxd_dir: PathBuf,
+
+        /// The output directory, laid out like the source one, or the output file for workbooks
+        #[arg(short, long, value_name = "PATH", required = true)]
+        out: PathBuf,
+
+        /// The format of the translation files. Workbooks have a sheet for every table
+        #[arg(long, value_enum, default_value_t = CliFileFormat::Json)]
+        format: CliFileFormat,
+
+        #[command(flatten)]
+        format_options: CliFormatOptions,
+
+        /// Group the entries by the sets of a table with two keys
+        #[arg(long)]
+        group_by_set: bool,
+
+        /// Also export the numbers and flags of every row to the JSON output
+        #[arg(long, conflicts_with = "group_by_set")]
+        all_columns: bool,
+    },
+    /// Import text into every NXD file in a directory, from a directory of translation files.
+    ImportAll {
+        /// The directory of the source NXD files
+        nxd_dir: PathBuf,
+
+        /// The directory of the translation files, laid out like the NXD one, or a workbook
+        #[arg(short, long, value_name = "PATH", required = true)]
+        input: PathBuf,
+
+        /// The output directory
+        #[arg(short, long, value_name = "DIR", required = true)]
+        out: PathBuf,
+
+        /// How to place the texts in the output files
+        #[arg(long, value_enum, default_value_t = CliTextLayout::Rebuild)]
+        layout: CliTextLayout,
+    },
+    /// Write an NXD file again from scratch, with a freshly laid out text area.
+    Rebuild {
+        /// The source NXD file
+        nxd: PathBuf,
+
         /// The output NXD file
         #[arg(short, long, value_name = "FILE", required = true)]
         out: PathBuf,
     },
+    /// Convert the keys of a JSON or PO file from the old row index based format.
+    Migrate {
+        /// The NXD file the translation was made for
+        nxd: PathBuf,
+
+        #[command(flatten)]
+        input: CliTranslationInput,
+
+        /// The output file, in the same format as the input
+        #[arg(short, long, value_name = "FILE", required = true)]
+        out: PathBuf,
+    },
+    /// Update a JSON or PO translation to a new version of its NXD file.
+    Update {
+        /// The new NXD file
+        nxd: PathBuf,
+
+        /// The NXD file the translation was made for, to find the changed texts.
+        /// Required for JSON files, as they don't contain the source texts.
+        #[arg(long, value_name = "FILE")]
+        old_nxd: Option<PathBuf>,
+
+        #[command(flatten)]
+        input: CliTranslationInput,
+
+        /// The output file, in the same format as the input
+        #[arg(short, long, value_name = "FILE", required = true)]
+        out: PathBuf,
+    },
+    /// Recover the translation of a modded NXD file, by comparing it with the original one.
+    Recover {
+        /// The original NXD file
+        nxd: PathBuf,
+
+        /// The modded NXD file of the same table
+        modded_nxd: PathBuf,
+
+        /// The output JSON file, with the modded texts only
+        #[arg(long, value_name = "FILE", required_unless_present = "out_po")]
+        out_json: Option<PathBuf>,
+
+        /// The output PO file, with the original texts translated to the modded ones
+        #[arg(long, value_name = "FILE")]
+        out_po: Option<PathBuf>,
+    },
+    /// Compare the texts of two versions of an NXD table.
+    Diff {
+        /// The old NXD file
+        old_nxd: PathBuf,
+
+        /// The new NXD file
+        new_nxd: PathBuf,
+
+        /// How to pair the rows of the two tables
+        #[arg(long, value_enum, default_value_t = CliDiffMatch::Key)]
+        match_by: CliDiffMatch,
+
+        /// Also write the differences to a JSON file
+        #[arg(long, value_name = "FILE")]
+        out_json: Option<PathBuf>,
+    },
+    /// Guess the column layout of a table that's not known yet.
+    Discover {
+        /// The source NXD file
+        nxd: PathBuf,
+
+        /// The output TOML schema file, instead of printing it
+        #[arg(short, long, value_name = "FILE")]
+        out: Option<PathBuf>,
+    },
 }
 
-#[derive(Args, Debug)]
+#[derive(Args, Debug, Default)]
 #[group(required = true, multiple = true)]
 pub struct CliExportOutput {
     /// The output JSON file
@@ -54,11 +212,151 @@ pub struct CliExportOutput {
     /// The output PO file
     #[arg(long, value_name = "FILE")]
     pub out_po: Option<PathBuf>,
+
+    /// The output XLIFF file
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["group_by_set", "all_columns"])]
+    pub out_xliff: Option<PathBuf>,
+
+    /// The output CSV file
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["group_by_set", "all_columns"])]
+    pub out_csv: Option<PathBuf>,
+
+    /// The output TSV file
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["group_by_set", "all_columns"])]
+    pub out_tsv: Option<PathBuf>,
+
+    /// The output Excel workbook, with a sheet for the table
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["group_by_set", "all_columns"])]
+    pub out_xlsx: Option<PathBuf>,
+
+    /// The output OpenDocument spreadsheet, with a sheet for the table
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["group_by_set", "all_columns"])]
+    pub out_ods: Option<PathBuf>,
+
+    /// The output TMX translation memory, which needs the translations of the table
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["group_by_set", "all_columns"])]
+    pub out_tmx: Option<PathBuf>,
+}
+
+#[derive(Args, Debug, Default)]
+#[group(multiple = false)]
+pub struct CliExportTarget {
+    /// The same table in another language, to fill the translations of the bilingual outputs
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["group_by_set", "all_columns"])]
+    pub target_nxd: Option<PathBuf>,
+
+    /// A translation file of the table, to fill the translations of the bilingual outputs.
+    /// Its format is found by its extension
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["group_by_set", "all_columns"])]
+    pub target_file: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct CliFormatOptions {
+    /// The version of the XLIFF output
+    #[arg(long, value_enum, default_value_t = CliXliffVersion::V1_2)]
+    pub xliff_version: CliXliffVersion,
+
+    /// The language of the source texts in the XLIFF and TMX output
+    #[arg(long, value_name = "LANG", default_value = "en")]
+    pub source_lang: String,
+
+    /// The language of the translations in the XLIFF and TMX output
+    #[arg(long, value_name = "LANG")]
+    pub target_lang: Option<String>,
+
+    /// Add the table, the row key and the column of every text to the CSV and TSV output
+    #[arg(long)]
+    pub csv_context: bool,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum CliXliffVersion {
+    #[value(name = "1.2")]
+    V1_2,
+    #[value(name = "2.0")]
+    V2_0,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum CliFileFormat {
+    Json,
+    Po,
+    Xliff,
+    Csv,
+    Tsv,
+    Xlsx,
+    Ods,
+}
+
+impl CliFileFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            CliFileFormat::Json => "json",
+            CliFileFormat::Po => "po",
+            CliFileFormat::Xliff => "xlf",
+            CliFileFormat::Csv => "csv",
+            CliFileFormat::Tsv => "tsv",
+            CliFileFormat::Xlsx => "xlsx",
+            CliFileFormat::Ods => "ods",
+        }
+    }
+
+    /// Finds the format of a file by its extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?;
+        Self::value_variants()
+            .iter()
+            .copied()
+            .find(|format| format.extension().eq_ignore_ascii_case(extension))
+    }
+}
+
+#[derive(Args, Debug, Default)]
+pub struct CliRowChanges {
+    /// Create new rows for keys that don't match any existing row
+    #[arg(long)]
+    pub add_rows: bool,
+
+    /// Remove the row with the given key(s), eg. `1042` or `30/7`
+    #[arg(long, value_name = "ROWKEY")]
+    pub remove_row: Vec<String>,
+}
+
+#[derive(Args, Debug, Default)]
+pub struct CliImportChecks {
+    /// Fail if two layers set the same key to different texts, instead of just reporting it
+    #[arg(long, requires = "layer")]
+    pub fail_on_conflict: bool,
+
+    /// Fail if a key doesn't match any text of the table, or a text has no translation
+    #[arg(long)]
+    pub strict: bool,
+
+    /// Take the keys as current ones, even if they could be the row positions of earlier versions
+    #[arg(long)]
+    pub current_keys: bool,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum CliDiffMatch {
+    /// By their row keys, so inserted or reordered rows are not reported as changed
+    Key,
+    /// By their position in the table
+    Index,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum CliTextLayout {
+    /// Write a new text area, with every text in it once
+    Rebuild,
+    /// Keep the original file as it is, and only change the texts that changed
+    Preserve,
 }
 
 #[derive(Args, Debug)]
 #[group(required = true, multiple = false)]
-pub struct CliInjectInput {
+pub struct CliTranslationInput {
     /// The input JSON file
     #[arg(long, value_name = "FILE")]
     pub json: Option<PathBuf>,
@@ -67,3 +365,66 @@ pub struct CliInjectInput {
     #[arg(long, value_name = "FILE")]
     pub po: Option<PathBuf>,
 }
+
+#[derive(Args, Clone, Debug, Default)]
+#[group(skip)]
+pub struct CliInjectInput {
+    /// The input JSON file
+    #[arg(long, value_name = "FILE", group = "input_file")]
+    pub json: Option<PathBuf>,
+
+    /// The input PO file
+    #[arg(long, value_name = "FILE", group = "input_file")]
+    pub po: Option<PathBuf>,
+
+    /// The input XLIFF file
+    #[arg(long, value_name = "FILE", group = "input_file")]
+    pub xliff: Option<PathBuf>,
+
+    /// The input CSV file, with `key` and `target` columns
+    #[arg(long, value_name = "FILE", group = "input_file")]
+    pub csv: Option<PathBuf>,
+
+    /// The input TSV file, with `key` and `target` columns
+    #[arg(long, value_name = "FILE", group = "input_file")]
+    pub tsv: Option<PathBuf>,
+
+    /// The input Excel workbook, read from the sheet of the table
+    #[arg(long, value_name = "FILE", group = "input_file")]
+    pub xlsx: Option<PathBuf>,
+
+    /// The input OpenDocument spreadsheet, read from the sheet of the table
+    #[arg(long, value_name = "FILE", group = "input_file")]
+    pub ods: Option<PathBuf>,
+
+    /// A translation file to layer on top of the previous ones, taking precedence over them.
+    /// Can be repeated, and used with or without one of the inputs above; the format of the
+    /// files is found by their extension
+    #[arg(long, value_name = "FILE", required_unless_present = "input_file")]
+    pub layer: Vec<PathBuf>,
+}
+
+impl CliInjectInput {
+    /// Takes a single translation file.
+    pub fn from_file(format: CliFileFormat, path: PathBuf) -> Self {
+        let mut input = Self::default();
+        let field = match format {
+            CliFileFormat::Json => &mut input.json,
+            CliFileFormat::Po => &mut input.po,
+            CliFileFormat::Xliff => &mut input.xliff,
+            CliFileFormat::Csv => &mut input.csv,
+            CliFileFormat::Tsv => &mut input.tsv,
+            CliFileFormat::Xlsx => &mut input.xlsx,
+            CliFileFormat::Ods => &mut input.ods,
+        };
+        *field = Some(path);
+        input
+    }
+
+    /// The files of the regular inputs, without the layers.
+    pub fn files(&self) -> impl Iterator<Item = &PathBuf> {
+        [&self.json, &self.po, &self.xliff, &self.csv, &self.tsv, &self.xlsx, &self.ods]
+            .into_iter()
+            .flatten()
+    }
+}
@@ -0,0 +1,171 @@
+// Copyright (C) 2025  Mátyás Mustoha
+
+use crate::{Error, cli::CliDiffMatch, path_to_tablename, read_nxd_rows, split_translation_key};
+use ffttic_nxdtext_core as nxd;
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+
+/// The texts that differ between two versions of a table, in the order of their rows.
+#[derive(Default)]
+pub struct TableDiff {
+    pub added: Vec<(String, String)>,
+    pub removed: Vec<(String, String)>,
+    /// The keys with their old and new texts
+    pub changed: Vec<(String, String, String)>,
+    pub unchanged: usize,
+}
+
+impl TableDiff {
+    pub fn new(old_rows: Vec<(String, String)>, new_rows: Vec<(String, String)>) -> Self {
+        let mut diff = TableDiff::default();
+        let mut old_texts = old_rows.iter().cloned().collect::<HashMap<_, _>>();
+
+        for (key, text) in new_rows {
+            match old_texts.remove(&key) {
+                Some(old_text) if old_text == text => diff.unchanged += 1,
+                Some(old_text) => diff.changed.push((key, old_text, text)),
+                None => diff.added.push((key, text)),
+            }
+        }
+        diff.removed = old_rows
+            .into_iter()
+            .filter(|(key, _)| old_texts.contains_key(key))
+            .collect();
+        diff
+    }
+
+    fn print(&self) {
+        if !self.added.is_empty() {
+            println!("Added {} text(s):", self.added.len());
+            for (key, text) in &self.added {
+                println!("  {}: {:?}", key, text);
+            }
+        }
+        if !self.removed.is_empty() {
+            println!("Removed {} text(s):", self.removed.len());
+            for (key, text) in &self.removed {
+                println!("  {}: {:?}", key, text);
+            }
+        }
+        if !self.changed.is_empty() {
+            println!("Changed {} text(s):", self.changed.len());
+            for (key, old_text, new_text) in &self.changed {
+                println!("  {}:", key);
+                println!("    - {:?}", old_text);
+                println!("    + {:?}", new_text);
+            }
+        }
+        println!(
+            "{} added, {} removed, {} changed, {} unchanged",
+            self.added.len(),
+            self.removed.len(),
+            self.changed.len(),
+            self.unchanged,
+        );
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        let texts = |entries: &[(String, String)]| {
+            entries
+                .iter()
+                .map(|(key, text)| (key.clone(), serde_json::Value::from(text.as_str())))
+                .collect::<serde_json::Map<_, _>>()
+        };
+        let changed = self
+            .changed
+            .iter()
+            .map(|(key, old_text, new_text)| {
+                let texts = serde_json::json!({ "old": old_text, "new": new_text });
+                (key.clone(), texts)
+            })
+            .collect::<serde_json::Map<_, _>>();
+
+        serde_json::json!({
+            "added": texts(&self.added),
+            "removed": texts(&self.removed),
+            "changed": changed,
+        })
+    }
+}
+
+
+/// Reads the texts of a table, with the row keys in their translation keys replaced by
+/// the position of the row, eg. `charaname/#0/2`.
+fn read_rows_by_index(
+    nxd_path: &Path,
+    schemas: &nxd::Schemas,
+) -> Result<Vec<(String, String)>, Error> {
+    let tablename = path_to_tablename(nxd_path)?;
+    let nxdfile = File::open(nxd_path)?;
+    let mut reader = BufReader::new(nxdfile);
+    let table = nxd::read_table(&mut reader, tablename, schemas)?;
+
+    let row_indices = table
+        .rows
+        .iter()
+        .enumerate()
+        .map(|(idx, row)| {
+            let row_key = match row.key2 {
+                Some(key2) => format!("{}/{}", row.key1, key2),
+                None => row.key1.to_string(),
+            };
+            (row_key, idx)
+        })
+        .collect::<HashMap<_, _>>();
+
+    let rows = read_nxd_rows(nxd_path, schemas)?
+        .into_iter()
+        .map(|(key, text)| {
+            let (table, row, column) = split_translation_key(&key);
+            let index_key = match row_indices.get(row) {
+                Some(idx) => format!("{}/#{}/{}", table, idx, column),
+                None => key.clone(),
+            };
+            (index_key, text)
+        })
+        .collect();
+    Ok(rows)
+}
+
+
+pub fn run(
+    old_nxd_path: &Path,
+    new_nxd_path: &Path,
+    schemas: &nxd::Schemas,
+    match_by: CliDiffMatch,
+    out_json: &Option<PathBuf>,
+) -> Result<(), Error> {
+    let old_tablename = path_to_tablename(old_nxd_path)?;
+    let new_tablename = path_to_tablename(new_nxd_path)?;
+    if old_tablename != new_tablename {
+        return Err(Error(format!(
+            "The files are of different tables: `{}` and `{}`",
+            old_tablename, new_tablename
+        )));
+    }
+
+    let read_rows = match match_by {
+        CliDiffMatch::Key => read_nxd_rows,
+        CliDiffMatch::Index => read_rows_by_index,
+    };
+    let diff = TableDiff::new(
+        read_rows(old_nxd_path, schemas)?,
+        read_rows(new_nxd_path, schemas)?,
+    );
+    diff.print();
+
+    if let Some(json_path) = out_json {
+        if let Some(parent) = json_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let json_content = serde_json::to_string_pretty(&diff.to_json())?;
+        let mut json_file = File::create(json_path)?;
+        json_file.write_all(json_content.as_bytes())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,30 @@
+// Copyright (C) 2025  Mátyás Mustoha
+
+use crate::{Error, path_to_tablename};
+use ffttic_nxdtext_core as nxd;
+use std::{
+    fs::{self, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+
+pub fn run(nxd_path: &Path, out_path: &Option<PathBuf>) -> Result<(), Error> {
+    let tablename = path_to_tablename(nxd_path)?;
+
+    let nxdfile = File::open(nxd_path)?;
+    let mut reader = BufReader::new(nxdfile);
+    let schema = nxd::discover_schema(&mut reader)?;
+    let toml = schema.to_toml(tablename);
+
+    match out_path {
+        Some(out_path) => {
+            if let Some(parent) = out_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(out_path, toml)?;
+        },
+        None => print!("{}", toml),
+    }
+    Ok(())
+}
@@ -1,15 +1,20 @@
 // Copyright (C) 2025  Mátyás Mustoha
 
-use crate::{Error, path_to_tablename};
+use crate::{
+    Error,
+    cli::{CliExportOutput, CliExportTarget, CliFileFormat, CliFormatOptions, CliInjectInput},
+    batch, import, is_pac_path, path_to_tablename, spreadsheet, tmx, workbook, xliff,
+};
 use ffttic_nxdtext_core as nxd;
 use std::{
+    collections::{HashMap, HashSet},
     fs::{self, File},
-    io::{BufReader, BufWriter, Write},
+    io::{BufReader, BufWriter, Cursor, Write},
     path::{Path, PathBuf},
 };
 
 
-fn save_json(rows: &[(String, String)], out_path: &Path) -> Result<(), Error> {
+pub fn save_json(rows: &[(String, String)], out_path: &Path) -> Result<(), Error> {
     let mut map = serde_json::Map::with_capacity(rows.len());
 
     for (key, text) in rows {
@@ -24,6 +29,63 @@ fn save_json(rows: &[(String, String)], out_path: &Path) -> Result<(), Error> {
 }
 
 
+fn value_to_json(value: &nxd::Value) -> serde_json::Value {
+    match value {
+        nxd::Value::Text(text) => serde_json::Value::String(text.clone()),
+        nxd::Value::Bool(value) => serde_json::Value::Bool(*value),
+        nxd::Value::U32(value) => serde_json::Value::from(*value),
+        nxd::Value::I32(value) => serde_json::Value::from(*value),
+        nxd::Value::F32(value) => {
+            // Go through the shortest text form, so eg. 0.1 doesn't become 0.10000000149011612
+            let value = value.to_string().parse::<f64>().unwrap_or_default();
+            serde_json::Value::from(value)
+        },
+    }
+}
+
+
+fn save_json_values(values: &[(String, nxd::Value)], out_path: &Path) -> Result<(), Error> {
+    let mut map = serde_json::Map::with_capacity(values.len());
+
+    for (key, value) in values {
+        map.insert(key.clone(), value_to_json(value));
+    }
+
+    let json_content = serde_json::to_string_pretty(&map)?;
+
+    let mut json_file = File::create(out_path)?;
+    json_file.write_all(json_content.as_bytes())?;
+    Ok(())
+}
+
+
+fn save_json_by_set(sets: &[(u32, Vec<(String, String)>)], out_path: &Path) -> Result<(), Error> {
+    let mut map = serde_json::Map::with_capacity(sets.len());
+
+    for (set_key, rows) in sets {
+        let mut set_map = serde_json::Map::with_capacity(rows.len());
+        for (key, text) in rows {
+            set_map.insert(key.clone(), serde_json::Value::String(text.clone()));
+        }
+        map.insert(set_key.to_string(), serde_json::Value::Object(set_map));
+    }
+
+    let json_content = serde_json::to_string_pretty(&map)?;
+
+    let mut json_file = File::create(out_path)?;
+    json_file.write_all(json_content.as_bytes())?;
+    Ok(())
+}
+
+
+fn write_po(catalog: &polib::catalog::Catalog, out_path: &Path) -> Result<(), Error> {
+    let file = File::create(out_path)?;
+    let mut writer = BufWriter::new(file);
+    polib::po_file::write(catalog, &mut writer)?;
+    Ok(())
+}
+
+
 fn save_po(rows: &[(String, String)], out_path: &Path) -> Result<(), Error> {
     let mut catalog = polib::catalog::Catalog::new(Default::default());
 
@@ -35,36 +97,333 @@ fn save_po(rows: &[(String, String)], out_path: &Path) -> Result<(), Error> {
         catalog.append_or_update(message);
     }
 
-    let file = File::create(out_path)?;
-    let mut writer = BufWriter::new(file);
-    polib::po_file::write(&catalog, &mut writer)?;
-    Ok(())
+    write_po(&catalog, out_path)
+}
+
+
+/// Reads the same table in another language, and reports the keys that are only
+/// present in one of the two tables.
+fn read_target_texts(
+    target_path: &Path,
+    tablename: &str,
+    schemas: &nxd::Schemas,
+    rows: &[(String, String)],
+) -> Result<HashMap<String, String>, Error> {
+    let target_file = File::open(target_path)?;
+    let mut target_reader = BufReader::new(target_file);
+    let target_texts = nxd::read_rows(&mut target_reader, tablename, schemas)?
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+
+    for (key, _) in rows.iter().filter(|(key, _)| !target_texts.contains_key(key)) {
+        eprintln!("Warning: `{}` is missing from the target table", key);
+    }
+    let source_keys = rows.iter().map(|(key, _)| key).collect::<HashSet<_>>();
+    let mut extra_keys = target_texts
+        .keys()
+        .filter(|key| !source_keys.contains(key))
+        .collect::<Vec<_>>();
+    extra_keys.sort();
+    for key in extra_keys {
+        eprintln!("Warning: `{}` is only present in the target table, and was skipped", key);
+    }
+
+    Ok(target_texts)
+}
+
+
+/// Reads the translations of a JSON, PO, XLIFF, CSV, TSV, XLSX or ODS file, and reports
+/// the keys that are not present in the table.
+fn read_translation_file(
+    path: &Path,
+    tablename: &str,
+    rows: &[(String, String)],
+) -> Result<HashMap<String, String>, Error> {
+    let format = CliFileFormat::from_path(path).ok_or_else(|| {
+        Error(format!("Unknown translation file format: {}", path.display()))
+    })?;
+    let input = CliInjectInput::from_file(format, path.to_path_buf());
+    let mut target_texts = HashMap::new();
+    import::load_input(&input, Some(tablename), &mut target_texts, &mut HashMap::new())?;
+
+    let source_keys = rows.iter().map(|(key, _)| key).collect::<HashSet<_>>();
+    let mut extra_keys = target_texts
+        .keys()
+        .filter(|key| !source_keys.contains(key))
+        .collect::<Vec<_>>();
+    extra_keys.sort();
+    for key in extra_keys {
+        eprintln!("Warning: `{}` is not present in the table, and was skipped", key);
+    }
+
+    Ok(target_texts)
+}
+
+
+/// Pairs the texts of the source table with the ones of the target table, by their keys.
+pub fn save_bilingual_po(
+    rows: &[(String, String)],
+    target_texts: &HashMap<String, String>,
+    out_path: &Path,
+) -> Result<(), Error> {
+    let mut catalog = polib::catalog::Catalog::new(Default::default());
+
+    for (key, text) in rows {
+        let message = polib::message::Message::build_singular()
+            .with_msgctxt(key.clone())
+            .with_msgid(text.clone())
+            .with_msgstr(target_texts.get(key).cloned().unwrap_or_default())
+            .done();
+        catalog.append_or_update(message);
+    }
+
+    write_po(&catalog, out_path)
+}
+
+
+fn save_po_by_set(sets: &[(u32, Vec<(String, String)>)], out_path: &Path) -> Result<(), Error> {
+    let mut catalog = polib::catalog::Catalog::new(Default::default());
+
+    for (set_key, rows) in sets {
+        for (key, text) in rows {
+            let message = polib::message::Message::build_singular()
+                .with_comments(format!("Set {}", set_key))
+                .with_msgctxt(key.clone())
+                .with_msgid(text.clone())
+                .done();
+            catalog.append_or_update(message);
+        }
+    }
+
+    write_po(&catalog, out_path)
 }
 
 
 pub fn run(
     nxd_path: &Path,
-    out_json: &Option<PathBuf>,
-    out_po: &Option<PathBuf>,
+    schemas: &nxd::Schemas,
+    output: &CliExportOutput,
+    format_options: &CliFormatOptions,
+    target: &CliExportTarget,
+    group_by_set: bool,
+    all_columns: bool,
 ) -> Result<(), Error> {
-    let tablename = path_to_tablename(&nxd_path)?;
+    if is_pac_path(nxd_path) {
+        let has_target = target.target_nxd.is_some() || target.target_file.is_some();
+        if group_by_set || all_columns || has_target {
+            return Err(Error(
+                "Archives can't be grouped by set, have all the columns or a target".to_owned(),
+            ));
+        }
+        return export_pac(nxd_path, schemas, output, format_options);
+    }
+
+    let tablename = path_to_tablename(nxd_path)?;
+    let (out_json, out_po) = (&output.out_json, &output.out_po);
 
     let nxdfile = File::open(nxd_path)?;
     let mut reader = BufReader::new(nxdfile);
-    let rows = nxd::read_rows(&mut reader, tablename)?;
 
-    if let Some(json_path) = out_json {
+    if group_by_set {
+        let sets = nxd::read_rows_by_set(&mut reader, tablename, schemas)?;
+
+        if let Some(json_path) = out_json {
+            if let Some(parent) = json_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            save_json_by_set(&sets, json_path)?;
+        }
+        if let Some(po_path) = out_po {
+            if let Some(parent) = po_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            save_po_by_set(&sets, po_path)?;
+        }
+        return Ok(());
+    }
+
+    if all_columns {
+        let values = nxd::read_values(&mut reader, tablename, schemas)?;
+
+        if let Some(json_path) = out_json {
+            if let Some(parent) = json_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            save_json_values(&values, json_path)?;
+        }
+        if let Some(po_path) = out_po {
+            if let Some(parent) = po_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let rows = values
+                .into_iter()
+                .filter_map(|(key, value)| match value {
+                    nxd::Value::Text(text) => Some((key, text)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            save_po(&rows, po_path)?;
+        }
+        return Ok(());
+    }
+
+    let rows = nxd::read_rows(&mut reader, tablename, schemas)?;
+    let target_texts = match (&target.target_nxd, &target.target_file) {
+        (Some(target_path), _) => Some(read_target_texts(target_path, tablename, schemas, &rows)?),
+        (None, Some(target_path)) => Some(read_translation_file(target_path, tablename, &rows)?),
+        (None, None) => None,
+    };
+
+    let sheets = [workbook::Sheet {
+        tablename,
+        rows: &rows,
+        target_texts: target_texts.as_ref(),
+    }];
+    save_text_outputs(tablename, &sheets, schemas, output, format_options)
+}
+
+
+/// Writes the texts of one or more tables to every requested output. Only the workbooks
+/// keep the tables apart, the other formats have them one after the other.
+fn save_text_outputs(
+    name: &str,
+    sheets: &[workbook::Sheet],
+    schemas: &nxd::Schemas,
+    output: &CliExportOutput,
+    format_options: &CliFormatOptions,
+) -> Result<(), Error> {
+    let rows = sheets
+        .iter()
+        .flat_map(|sheet| sheet.rows.iter().cloned())
+        .collect::<Vec<_>>();
+    let target_texts = match sheets.iter().any(|sheet| sheet.target_texts.is_some()) {
+        true => Some(
+            sheets
+                .iter()
+                .flat_map(|sheet| sheet.target_texts.into_iter().flatten())
+                .map(|(key, text)| (key.clone(), text.clone()))
+                .collect::<HashMap<_, _>>(),
+        ),
+        false => None,
+    };
+
+    if let Some(json_path) = &output.out_json {
         if let Some(parent) = json_path.parent() {
             fs::create_dir_all(parent)?;
         }
         save_json(&rows, json_path)?;
     }
-    if let Some(po_path) = out_po {
+    if let Some(po_path) = &output.out_po {
         if let Some(parent) = po_path.parent() {
             fs::create_dir_all(parent)?;
         }
-        save_po(&rows, po_path)?;
+        match &target_texts {
+            Some(target_texts) => save_bilingual_po(&rows, target_texts, po_path)?,
+            None => save_po(&rows, po_path)?,
+        }
+    }
+    if let Some(xliff_path) = &output.out_xliff {
+        if let Some(parent) = xliff_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let target_texts = target_texts.as_ref();
+        xliff::save_xliff(name, &rows, target_texts, schemas, format_options, xliff_path)?;
+    }
+    for (table_path, delimiter) in [(&output.out_csv, b','), (&output.out_tsv, b'\t')] {
+        if let Some(table_path) = table_path {
+            if let Some(parent) = table_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            spreadsheet::save_table(
+                &rows,
+                target_texts.as_ref(),
+                format_options.csv_context,
+                delimiter,
+                table_path,
+            )?;
+        }
+    }
+
+    if let Some(xlsx_path) = &output.out_xlsx {
+        if let Some(parent) = xlsx_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        workbook::save_xlsx(sheets, xlsx_path)?;
+    }
+    if let Some(ods_path) = &output.out_ods {
+        if let Some(parent) = ods_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        workbook::save_ods(sheets, ods_path)?;
+    }
+    if let Some(tmx_path) = &output.out_tmx {
+        let target_texts = target_texts.as_ref().ok_or_else(|| {
+            Error(
+                "TMX files need the translations, from `--target-nxd` or `--target-file`"
+                    .to_owned(),
+            )
+        })?;
+        if let Some(parent) = tmx_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        tmx::save_tmx(&rows, target_texts, format_options, tmx_path)?;
     }
 
     Ok(())
 }
+
+
+/// Exports the texts of every table in an archive that has a layout. Entries that can't be
+/// read, like the compressed ones, are skipped.
+fn export_pac(
+    pac_path: &Path,
+    schemas: &nxd::Schemas,
+    output: &CliExportOutput,
+    format_options: &CliFormatOptions,
+) -> Result<(), Error> {
+    let mut summary = batch::Summary::default();
+    let mut tables: Vec<(String, Vec<(String, String)>)> = Vec::new();
+
+    let pacfile = File::open(pac_path)?;
+    let mut reader = BufReader::new(pacfile);
+    for entry in nxd::read_pac_entries(&mut reader)? {
+        let entry_path = PathBuf::from(&entry.path);
+        if !batch::is_nxd_path(&entry_path) {
+            continue;
+        }
+        if let Some(reason) = batch::skip_reason(&entry_path, schemas) {
+            summary.skipped.push((entry_path, reason));
+            continue;
+        }
+        let tablename = path_to_tablename(&entry_path)?.to_owned();
+        if tables.iter().any(|(name, _)| *name == tablename) {
+            let reason = format!("The table `{}` was already exported", tablename);
+            summary.skipped.push((entry_path, reason));
+            continue;
+        }
+
+        let result = nxd::extract_pac_entry(&mut reader, &entry).and_then(|nxd_data| {
+            nxd::read_rows(&mut Cursor::new(nxd_data), &tablename, schemas)
+        });
+        match result {
+            Ok(rows) => {
+                summary.done += 1;
+                tables.push((tablename, rows));
+            },
+            Err(err) => summary.failed.push((entry_path, err.to_string())),
+        }
+    }
+
+    let sheets = tables
+        .iter()
+        .map(|(tablename, rows)| workbook::Sheet {
+            tablename,
+            rows,
+            target_texts: None,
+        })
+        .collect::<Vec<_>>();
+    let name = pac_path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default();
+    save_text_outputs(name, &sheets, schemas, output, format_options)?;
+
+    summary.finish("Exported")
+}
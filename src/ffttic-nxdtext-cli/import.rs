@@ -1,25 +1,71 @@
 // Copyright (C) 2025  Mátyás Mustoha
 
-use crate::{Error, path_to_tablename};
+use crate::{
+    Error,
+    cli::{CliFileFormat, CliImportChecks, CliInjectInput, CliRowChanges},
+    batch, is_pac_path, path_to_tablename, spreadsheet, workbook, xliff,
+};
 use ffttic_nxdtext_core as nxd;
 use std::{
     collections::HashMap,
     fs::{self, File},
-    io::{BufReader, Write},
+    io::{BufReader, Cursor, Seek, Write},
     path::{Path, PathBuf},
 };
 
 
-fn load_json(path: &Path, overrides: &mut HashMap<String, String>) -> Result<(), Error> {
-    let file = File::open(path)?;
-    let map: HashMap<String, String> = serde_json::from_reader(file)?;
+fn json_to_value(key: &str, val: &serde_json::Value) -> Result<nxd::Value, Error> {
+    let value = match val {
+        serde_json::Value::Bool(value) => Some(nxd::Value::Bool(*value)),
+        serde_json::Value::Number(number) => {
+            if let Some(value) = number.as_u64().and_then(|value| u32::try_from(value).ok()) {
+                Some(nxd::Value::U32(value))
+            } else if let Some(value) = number.as_i64().and_then(|value| i32::try_from(value).ok()) {
+                Some(nxd::Value::I32(value))
+            } else {
+                number.as_f64().map(|value| nxd::Value::F32(value as f32))
+            }
+        },
+        _ => None,
+    };
+    value.ok_or_else(|| Error(format!("The value of `{}` is neither a text nor a number", key)))
+}
+
+
+/// Collects the texts and numbers of a JSON object. Nested objects, like the
+/// sets of an export grouped by set, are flattened.
+fn collect_json_values(
+    map: serde_json::Map<String, serde_json::Value>,
+    overrides: &mut HashMap<String, String>,
+    value_overrides: &mut HashMap<String, nxd::Value>,
+) -> Result<(), Error> {
     for (key, val) in map {
-        overrides.insert(key, val);
+        match val {
+            serde_json::Value::String(text) => {
+                overrides.insert(key, text);
+            },
+            serde_json::Value::Object(inner_map) => collect_json_values(inner_map, overrides, value_overrides)?,
+            _ => {
+                let value = json_to_value(&key, &val)?;
+                value_overrides.insert(key, value);
+            },
+        }
     }
     Ok(())
 }
 
 
+fn load_json(
+    path: &Path,
+    overrides: &mut HashMap<String, String>,
+    value_overrides: &mut HashMap<String, nxd::Value>,
+) -> Result<(), Error> {
+    let file = File::open(path)?;
+    let map: serde_json::Map<String, serde_json::Value> = serde_json::from_reader(file)?;
+    collect_json_values(map, overrides, value_overrides)
+}
+
+
 fn load_po(path: &Path, overrides: &mut HashMap<String, String>) -> Result<(), Error> {
     let po_options = polib::po_file::POParseOptions {
         message_body_only: true,
@@ -41,25 +87,347 @@ fn load_po(path: &Path, overrides: &mut HashMap<String, String>) -> Result<(), E
 }
 
 
+/// Reads the texts and numbers of the given translation files. Workbooks are read from
+/// the sheet of the table, or without a table name, from every sheet.
+pub fn load_input(
+    input: &CliInjectInput,
+    tablename: Option<&str>,
+    overrides: &mut HashMap<String, String>,
+    value_overrides: &mut HashMap<String, nxd::Value>,
+) -> Result<(), Error> {
+    if let Some(json_path) = &input.json {
+        load_json(json_path, overrides, value_overrides)?;
+    }
+    if let Some(po_path) = &input.po {
+        load_po(po_path, overrides)?;
+    }
+    if let Some(xliff_path) = &input.xliff {
+        xliff::load_xliff(xliff_path, overrides)?;
+    }
+    if let Some(csv_path) = &input.csv {
+        spreadsheet::load_table(csv_path, b',', overrides)?;
+    }
+    if let Some(tsv_path) = &input.tsv {
+        spreadsheet::load_table(tsv_path, b'\t', overrides)?;
+    }
+    for workbook_path in [&input.xlsx, &input.ods].into_iter().flatten() {
+        let sheet_names = match tablename {
+            Some(tablename) => vec![tablename.to_owned()],
+            None => workbook::sheet_names(workbook_path)?,
+        };
+        for sheet_name in &sheet_names {
+            workbook::load_workbook(workbook_path, sheet_name, overrides)?;
+        }
+    }
+    Ok(())
+}
+
+
+/// Prints what the import did. The texts without a translation are only listed on request,
+/// as partial translations can have a lot of them.
+fn print_report(report: &nxd::ImportReport, list_missing: bool) {
+    println!(
+        "Applied {} translation(s), {} text(s) have none",
+        report.applied.len(),
+        report.missing.len()
+    );
+    if list_missing && !report.missing.is_empty() {
+        println!("Texts without a translation:");
+        for key in &report.missing {
+            println!("  {}", key);
+        }
+    }
+    if !report.unknown.is_empty() {
+        println!(
+            "Skipped {} unknown key(s), not matching any cell of the table:",
+            report.unknown.len()
+        );
+        for key in &report.unknown {
+            println!("  {}", key);
+        }
+    }
+    if !report.moved.is_empty() {
+        println!(
+            "Moved {} text(s) to the end of the file, as they didn't fit in place:",
+            report.moved.len()
+        );
+        for key in &report.moved {
+            println!("  {}", key);
+        }
+    }
+}
+
+
+/// The texts and numbers to import into a table.
+#[derive(Default)]
+struct Overrides {
+    texts: HashMap<String, String>,
+    values: HashMap<String, nxd::Value>,
+    /// The keys that two layers set differently
+    conflicts: Vec<String>,
+}
+
+impl Overrides {
+    /// Reads the input files, and the layers on top of them. The regular inputs are the
+    /// first layer, so the layers on top of them are checked for conflicts with them too.
+    fn load(input: &CliInjectInput, tablename: Option<&str>) -> Result<Self, Error> {
+        let mut layers = Vec::new();
+        let base_files = input.files().map(|path| path.display().to_string()).collect::<Vec<_>>();
+        if !base_files.is_empty() {
+            let base_input = CliInjectInput {
+                layer: Vec::new(),
+                ..input.clone()
+            };
+            layers.push((base_files.join(", "), base_input));
+        }
+        for path in &input.layer {
+            let format = CliFileFormat::from_path(path).ok_or_else(|| {
+                Error(format!("Unknown translation file format: {}", path.display()))
+            })?;
+            let layer_input = CliInjectInput::from_file(format, path.clone());
+            layers.push((path.display().to_string(), layer_input));
+        }
+
+        load_layers(&layers, tablename)
+    }
+
+    /// Takes the keys of a single table.
+    fn for_table(&self, tablename: &str) -> Self {
+        let prefix = format!("{}/", tablename);
+        let in_table = |key: &String| key.starts_with(&prefix);
+        Self {
+            texts: self
+                .texts
+                .iter()
+                .filter(|(key, _)| in_table(key))
+                .map(|(key, text)| (key.clone(), text.clone()))
+                .collect(),
+            values: self
+                .values
+                .iter()
+                .filter(|(key, _)| in_table(key))
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+            conflicts: self.conflicts.iter().filter(|key| in_table(key)).cloned().collect(),
+        }
+    }
+
+    fn check_conflicts(&self, checks: &CliImportChecks) -> Result<(), Error> {
+        match checks.fail_on_conflict && !self.conflicts.is_empty() {
+            true => Err(Error(format!(
+                "Found {} conflict(s) between the layers",
+                self.conflicts.len()
+            ))),
+            false => Ok(()),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.texts.is_empty() && self.values.is_empty()
+    }
+}
+
+
+/// Reads the inputs on top of each other, the later ones taking precedence. Keys that two
+/// layers set to different texts or numbers are reported with the names of the layers.
+fn load_layers(
+    layers: &[(String, CliInjectInput)],
+    tablename: Option<&str>,
+) -> Result<Overrides, Error> {
+    let mut overrides = Overrides::default();
+    // The layer that set the current text or number of every key
+    let mut text_layers = HashMap::new();
+    let mut value_layers = HashMap::new();
+
+    for (layer_idx, (layer_name, input)) in layers.iter().enumerate() {
+        let mut layer = Overrides::default();
+        load_input(input, tablename, &mut layer.texts, &mut layer.values)?;
+
+        let mut layer_conflicts = Vec::new();
+        for (key, text) in layer.texts {
+            let prev_layer = text_layers.insert(key.clone(), layer_idx);
+            if let Some(prev_layer) = prev_layer
+                && overrides.texts.get(&key) != Some(&text)
+            {
+                layer_conflicts.push((key.clone(), prev_layer));
+            }
+            overrides.texts.insert(key, text);
+        }
+        for (key, value) in layer.values {
+            let prev_layer = value_layers.insert(key.clone(), layer_idx);
+            if let Some(prev_layer) = prev_layer
+                && overrides.values.get(&key) != Some(&value)
+            {
+                layer_conflicts.push((key.clone(), prev_layer));
+            }
+            overrides.values.insert(key, value);
+        }
+
+        layer_conflicts.sort();
+        for (key, prev_layer) in layer_conflicts {
+            eprintln!(
+                "Warning: `{}` is set differently by {} and {}, the latter is used",
+                key,
+                layers[prev_layer].0,
+                layer_name
+            );
+            overrides.conflicts.push(key);
+        }
+    }
+    Ok(overrides)
+}
+
+
+fn parse_row_key(row_key: &str) -> Result<(u32, Option<u32>), Error> {
+    let invalid_key = || Error(format!("Invalid row key `{}`", row_key));
+    let parse_part = |part: &str| part.parse::<u32>().map_err(|_| invalid_key());
+
+    match row_key.split_once('/') {
+        Some((key1, key2)) => Ok((parse_part(key1)?, Some(parse_part(key2)?))),
+        None => Ok((parse_part(row_key)?, None)),
+    }
+}
+
+
+/// Applies the overrides to the contents of an NXD file, and reports what was done.
+fn import_table(
+    nxd_data: Vec<u8>,
+    tablename: &str,
+    schemas: &nxd::Schemas,
+    overrides: &Overrides,
+    row_changes: &CliRowChanges,
+    layout: nxd::TextLayout,
+    checks: &CliImportChecks,
+) -> Result<Vec<u8>, Error> {
+    let (text_overrides, value_overrides) = (&overrides.texts, &overrides.values);
+    let (add_rows, remove_rows) = (row_changes.add_rows, &row_changes.remove_row);
+
+    // Numbers are written in place first, then the text is handled on top of that
+    let (nxd_data, unknown_values) = {
+        let mut reader = Cursor::new(nxd_data);
+        if !checks.current_keys {
+            nxd::check_legacy_keys(&mut reader, tablename, schemas, text_overrides)?;
+            reader.rewind()?;
+        }
+        nxd::update_values(&mut reader, tablename, schemas, value_overrides)?
+    };
+    let mut reader = Cursor::new(nxd_data);
+    let (out_buf, mut report) = if add_rows || !remove_rows.is_empty() {
+        nxd::check_regions(&mut reader, tablename, schemas)?;
+        reader.rewind()?;
+        let mut table = nxd::read_table(&mut reader, tablename, schemas)?;
+        for row_key in remove_rows {
+            let (key1, key2) = parse_row_key(row_key)?;
+            if table.remove_row(key1, key2).is_none() {
+                eprintln!("Warning: No row with the key `{}` to remove", row_key);
+            }
+        }
+        let report = nxd::update_table(&mut table, tablename, text_overrides, add_rows)?;
+        (nxd::write_table(&table)?, report)
+    } else {
+        nxd::update_rows(&mut reader, tablename, schemas, text_overrides, layout)?
+    };
+    report.unknown.extend(unknown_values);
+    report.unknown.sort();
+    report.unknown.dedup();
+
+    print_report(&report, checks.strict);
+    if checks.strict && (!report.unknown.is_empty() || !report.missing.is_empty()) {
+        return Err(Error(format!(
+            "Found {} unknown key(s) and {} text(s) without a translation",
+            report.unknown.len(),
+            report.missing.len()
+        )));
+    }
+    Ok(out_buf)
+}
+
+
+/// Imports into every table of an archive that has a layout and a translation, and
+/// writes the archive again with the new tables. The inputs are read once, with every
+/// sheet of the workbooks, and are split by the tables of their keys.
+fn import_pac(
+    pac_path: &Path,
+    schemas: &nxd::Schemas,
+    input: &CliInjectInput,
+    out_pac: &Path,
+    layout: nxd::TextLayout,
+    checks: &CliImportChecks,
+) -> Result<(), Error> {
+    let mut summary = batch::Summary::default();
+    let mut replacements = HashMap::new();
+    let all_overrides = Overrides::load(input, None)?;
+
+    let pacfile = File::open(pac_path)?;
+    let mut reader = BufReader::new(pacfile);
+    for entry in nxd::read_pac_entries(&mut reader)? {
+        let entry_path = PathBuf::from(&entry.path);
+        if !batch::is_nxd_path(&entry_path) {
+            continue;
+        }
+        if let Some(reason) = batch::skip_reason(&entry_path, schemas) {
+            summary.skipped.push((entry_path, reason));
+            continue;
+        }
+        let tablename = path_to_tablename(&entry_path)?;
+
+        let overrides = all_overrides.for_table(tablename);
+        if overrides.is_empty() {
+            summary.skipped.push((entry_path, "No translation".to_owned()));
+            continue;
+        }
+
+        println!("{}:", entry.path);
+        let result = overrides
+            .check_conflicts(checks)
+            .and_then(|_| Ok(nxd::extract_pac_entry(&mut reader, &entry)?))
+            .and_then(|nxd_data| {
+                let no_row_changes = CliRowChanges::default();
+                let (overrides, checks) = (&overrides, checks);
+                import_table(nxd_data, tablename, schemas, overrides, &no_row_changes, layout, checks)
+            });
+        match result {
+            Ok(out_buf) => {
+                summary.done += 1;
+                replacements.insert(entry.path.clone(), out_buf);
+            },
+            Err(err) => summary.failed.push((entry_path, err.0)),
+        }
+    }
+
+    let out_buf = nxd::repack_pac(&mut reader, &replacements)?;
+    if let Some(parent) = out_pac.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut out_file = File::create(out_pac)?;
+    out_file.write_all(&out_buf)?;
+
+    summary.finish("Imported")
+}
+
+
 pub fn run(
     nxd_path: &Path,
-    in_json: &Option<PathBuf>,
-    in_po: &Option<PathBuf>,
+    schemas: &nxd::Schemas,
+    input: &CliInjectInput,
     out_nxd: &Path,
+    row_changes: &CliRowChanges,
+    layout: nxd::TextLayout,
+    checks: &CliImportChecks,
 ) -> Result<(), Error> {
-    let tablename = path_to_tablename(&nxd_path)?;
-
-    let mut text_overrides = HashMap::new();
-    if let Some(json_path) = in_json {
-        load_json(json_path, &mut text_overrides)?;
-    }
-    if let Some(po_path) = in_po {
-        load_po(po_path, &mut text_overrides)?;
+    if is_pac_path(nxd_path) {
+        if row_changes.add_rows || !row_changes.remove_row.is_empty() {
+            return Err(Error("Rows can't be added to or removed from archives".to_owned()));
+        }
+        return import_pac(nxd_path, schemas, input, out_nxd, layout, checks);
     }
 
-    let nxdfile = File::open(nxd_path)?;
-    let mut reader = BufReader::new(nxdfile);
-    let out_buf = nxd::update_rows(&mut reader, tablename, &text_overrides)?;
+    let tablename = path_to_tablename(nxd_path)?;
+    let overrides = Overrides::load(input, Some(tablename))?;
+    overrides.check_conflicts(checks)?;
+    let nxd_data = fs::read(nxd_path)?;
+    let out_buf =
+        import_table(nxd_data, tablename, schemas, &overrides, row_changes, layout, checks)?;
 
     if let Some(parent) = out_nxd.parent() {
         fs::create_dir_all(parent)?;
@@ -69,3 +437,214 @@ pub fn run(
 
     Ok(())
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    const TABLENAME: &str = "charaname";
+
+    /// A `charaname` table with the given names, in rows keyed from 10.
+    fn charaname_nxd(names: &[&str]) -> Vec<u8> {
+        let rows = names
+            .iter()
+            .enumerate()
+            .map(|(row_idx, name)| nxd::NxdRow {
+                key1: 10 + row_idx as u32,
+                key2: None,
+                cells: vec![
+                    nxd::CellValue::Bool32(1),
+                    nxd::CellValue::EmptyStr,
+                    nxd::CellValue::Str(name.to_string()),
+                    nxd::CellValue::Skip32(0),
+                    nxd::CellValue::Skip32(0),
+                    nxd::CellValue::Bool32(0),
+                ],
+            })
+            .collect();
+        let table = nxd::NxdTable {
+            header: nxd::NxdHeader {
+                row_type: nxd::NxdRowType::SingleKey,
+                localization: nxd::NxdLocalizationType::SingleKeyLocalized,
+                uses_base_rowid: false,
+                base_rowid: 0,
+                unknown: [0; 4],
+            },
+            columns: nxd::Schemas::builtin().get(TABLENAME).unwrap().cells(),
+            set_infos: Vec::new(),
+            rows,
+        };
+        nxd::write_table(&table).unwrap()
+    }
+
+    fn read_texts(nxd_data: &[u8]) -> Vec<String> {
+        nxd::read_rows(&mut Cursor::new(nxd_data), TABLENAME, &nxd::Schemas::builtin())
+            .unwrap()
+            .into_iter()
+            .map(|(_, text)| text)
+            .collect()
+    }
+
+    /// Writes the files into a new directory under the system's temp directory.
+    fn temp_dir(name: &str, files: &[(&str, &[u8])]) -> PathBuf {
+        let dir = env::temp_dir().join(format!("nxdtext-import-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for (file_name, content) in files {
+            fs::write(dir.join(file_name), content).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn layers_on_top_of_input() {
+        let dir = temp_dir(
+            "layers",
+            &[
+                ("base.json", br#"{"charaname/10/2": "Ramsa", "charaname/11/2": "Delita"}"#),
+                ("fixes.json", br#"{"charaname/10/2": "Ramza", "charaname/10/0": 0}"#),
+                ("platform.csv", b"key,target\ncharaname/11/2,Delita\n"),
+            ],
+        );
+        let layers = vec![dir.join("fixes.json"), dir.join("platform.csv")];
+        let fail_on_conflict = CliImportChecks {
+            fail_on_conflict: true,
+            ..Default::default()
+        };
+
+        // The regular input is the first layer, just like when given as one
+        let with_input = CliInjectInput {
+            json: Some(dir.join("base.json")),
+            layer: layers.clone(),
+            ..Default::default()
+        };
+        let layers_only = CliInjectInput {
+            layer: [vec![dir.join("base.json")], layers].concat(),
+            ..Default::default()
+        };
+        for input in [with_input, layers_only] {
+            let overrides = Overrides::load(&input, Some(TABLENAME)).unwrap();
+            assert_eq!(overrides.texts["charaname/10/2"], "Ramza");
+            assert_eq!(overrides.texts["charaname/11/2"], "Delita");
+            assert_eq!(overrides.values["charaname/10/0"], nxd::Value::U32(0));
+            assert_eq!(overrides.conflicts, ["charaname/10/2"]);
+            assert!(overrides.check_conflicts(&CliImportChecks::default()).is_ok());
+            assert!(overrides.check_conflicts(&fail_on_conflict).is_err());
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn strict_report() {
+        let nxd_data = charaname_nxd(&["Ramza", "Delita"]);
+        let overrides = Overrides {
+            texts: HashMap::from([
+                ("charaname/10/2".to_owned(), "Ramsa".to_owned()),
+                ("charaname/12/2".to_owned(), "Nobody".to_owned()),
+            ]),
+            values: HashMap::from([("charaname/13/0".to_owned(), nxd::Value::U32(0))]),
+            ..Default::default()
+        };
+        let schemas = nxd::Schemas::builtin();
+        let (row_changes, layout) = (CliRowChanges::default(), nxd::TextLayout::Rebuild);
+        let import = |checks: &CliImportChecks| {
+            let nxd_data = nxd_data.clone();
+            import_table(nxd_data, TABLENAME, &schemas, &overrides, &row_changes, layout, checks)
+        };
+
+        let out_buf = import(&CliImportChecks::default()).unwrap();
+        assert_eq!(read_texts(&out_buf), ["Ramsa", "Delita"]);
+
+        let strict = CliImportChecks {
+            strict: true,
+            ..Default::default()
+        };
+        let err = import(&strict).unwrap_err();
+        assert_eq!(err.0, "Found 2 unknown key(s) and 1 text(s) without a translation");
+    }
+
+    /// Builds an archive of uncompressed files.
+    fn build_pac(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let (header_size, entry_size) = (0x400, 0x38);
+        let names = files
+            .iter()
+            .flat_map(|(path, _)| path.bytes().chain([0]))
+            .collect::<Vec<_>>();
+        let mut name_pos = header_size + files.len() * entry_size;
+        let mut data_pos = name_pos + names.len();
+
+        let mut header = vec![0; header_size];
+        header[..4].copy_from_slice(b"PACK");
+        header[4..8].copy_from_slice(&(header_size as u32).to_le_bytes());
+        header[8..12].copy_from_slice(&(files.len() as u32).to_le_bytes());
+
+        let mut entries = Vec::new();
+        for (path, data) in files {
+            entries.extend((data.len() as u32).to_le_bytes());
+            entries.extend([0; 4]);
+            for field in [data.len(), name_pos, data_pos] {
+                entries.extend((field as u64).to_le_bytes());
+            }
+            // No compression chunks, name hash or checksum
+            entries.extend([0; 0x18]);
+            name_pos += path.len() + 1;
+            data_pos += data.len();
+        }
+
+        let contents = files.iter().flat_map(|(_, data)| data.iter().copied());
+        let mut pac = [header, entries, names, contents.collect()].concat();
+        let pack_size = pac.len() as u64;
+        pac[0x10..0x18].copy_from_slice(&pack_size.to_le_bytes());
+        pac
+    }
+
+    fn extract_all(pac_path: &Path) -> HashMap<String, Vec<u8>> {
+        let mut reader = Cursor::new(fs::read(pac_path).unwrap());
+        nxd::read_pac_entries(&mut reader)
+            .unwrap()
+            .iter()
+            .map(|entry| (entry.path.clone(), nxd::extract_pac_entry(&mut reader, entry).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn import_into_archive() {
+        let pac = build_pac(&[
+            ("nxd/charaname.nxd", &charaname_nxd(&["Ramza", "Delita"])),
+            ("sound/other.bin", b"other"),
+        ]);
+        let dir = temp_dir(
+            "pac",
+            &[
+                ("in.pac", &pac),
+                ("base.json", br#"{"charaname/10/2": "Ramsa", "uisubtitles/30/1/2": "Salut"}"#),
+                ("fixes.json", br#"{"charaname/11/2": "Dilita", "charaname/10/2": "Ramza"}"#),
+            ],
+        );
+        let (schemas, layout) = (nxd::Schemas::builtin(), nxd::TextLayout::Rebuild);
+        let mut input = CliInjectInput {
+            json: Some(dir.join("base.json")),
+            ..Default::default()
+        };
+
+        let (in_pac, out_pac) = (dir.join("in.pac"), dir.join("out.pac"));
+        import_pac(&in_pac, &schemas, &input, &out_pac, layout, &Default::default()).unwrap();
+        let extracted = extract_all(&out_pac);
+        assert_eq!(read_texts(&extracted["nxd/charaname.nxd"]), ["Ramsa", "Delita"]);
+        assert_eq!(extracted["sound/other.bin"], b"other");
+
+        // A conflict fails its table, and the archive is written without it
+        input.layer.push(dir.join("fixes.json"));
+        let checks = CliImportChecks {
+            fail_on_conflict: true,
+            ..Default::default()
+        };
+        let result = import_pac(&in_pac, &schemas, &input, &out_pac, layout, &checks);
+        assert!(result.is_err());
+        let extracted = extract_all(&out_pac);
+        assert_eq!(read_texts(&extracted["nxd/charaname.nxd"]), ["Ramza", "Delita"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -15,20 +15,40 @@
 
 #![forbid(unsafe_code)]
 
+mod batch;
 mod cli;
+mod diff;
+mod discover;
 mod error;
 mod export;
 mod import;
+mod migrate;
+mod rebuild;
+mod recover;
+mod spreadsheet;
+mod tmx;
+mod update;
+mod workbook;
+mod xliff;
 
 use crate::{
-    cli::{Cli, CliCommand},
+    cli::{Cli, CliCommand, CliTextLayout},
     error::Error,
 };
 use clap::Parser;
-use std::path::Path;
+use ffttic_nxdtext_core as nxd;
+use std::{fs::File, io::BufReader, path::Path};
+
+
+fn is_pac_path(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("pac"))
+}
 
 
 fn path_to_tablename(path: &Path) -> Result<&str, Error> {
+    if is_pac_path(path) {
+        return Err(nxd::NxdError::PacArchive.into());
+    }
     path.file_name()
         .and_then(|os_str| os_str.to_str())
         .map(|s| s.split_once('.').map(|(first, _)| first).unwrap_or(s))
@@ -38,13 +58,109 @@ fn path_to_tablename(path: &Path) -> Result<&str, Error> {
 }
 
 
+fn read_nxd_rows(nxd_path: &Path, schemas: &nxd::Schemas) -> Result<Vec<(String, String)>, Error> {
+    let tablename = path_to_tablename(nxd_path)?;
+    let nxdfile = File::open(nxd_path)?;
+    let mut reader = BufReader::new(nxdfile);
+    Ok(nxd::read_rows(&mut reader, tablename, schemas)?)
+}
+
+
+/// Splits a translation key into its table, row key and column parts.
+fn split_translation_key(key: &str) -> (&str, &str, &str) {
+    let (table, rest) = key.split_once('/').unwrap_or((key, ""));
+    let (row, column) = rest.rsplit_once('/').unwrap_or(("", rest));
+    (table, row, column)
+}
+
+
+fn load_schemas(args: &Cli) -> Result<nxd::Schemas, Error> {
+    let mut schemas = match args.no_builtin_schemas {
+        true => nxd::Schemas::default(),
+        false => nxd::Schemas::builtin(),
+    };
+    for path in &args.schema {
+        let (loaded_schemas, warnings) = nxd::Schemas::load(path)?;
+        for warning in warnings {
+            eprintln!("Warning: {}", warning);
+        }
+        schemas.merge(loaded_schemas);
+    }
+    Ok(schemas)
+}
+
+
+fn text_layout(layout: CliTextLayout) -> nxd::TextLayout {
+    match layout {
+        CliTextLayout::Rebuild => nxd::TextLayout::Rebuild,
+        CliTextLayout::Preserve => nxd::TextLayout::Preserve,
+    }
+}
+
+
 fn inner_main(args: Cli) -> Result<(), Error> {
+    let schemas = load_schemas(&args)?;
+
     match &args.command {
-        CliCommand::Export { nxd, output } => {
-            export::run(nxd, &output.out_json, &output.out_po)?;
+        CliCommand::Export {
+            nxd,
+            output,
+            format_options,
+            target,
+            group_by_set,
+            all_columns,
+        } => {
+            export::run(
+                nxd,
+                &schemas,
+                output,
+                format_options,
+                target,
+                *group_by_set,
+                *all_columns,
+            )?;
+        },
+        CliCommand::Import { nxd, input, out, row_changes, layout, checks } => {
+            import::run(nxd, &schemas, input, out, row_changes, text_layout(*layout), checks)?;
+        },
+        CliCommand::ExportAll {
+            nxd_dir,
+            out,
+            format,
+            format_options,
+            group_by_set,
+            all_columns,
+        } => {
+            batch::export_all(
+                nxd_dir,
+                &schemas,
+                out,
+                *format,
+                format_options,
+                *group_by_set,
+                *all_columns,
+            )?;
+        },
+        CliCommand::ImportAll { nxd_dir, input, out, layout } => {
+            batch::import_all(nxd_dir, &schemas, input, out, text_layout(*layout))?;
+        },
+        CliCommand::Rebuild { nxd, out } => {
+            rebuild::run(nxd, &schemas, out)?;
+        },
+        CliCommand::Migrate { nxd, input, out } => {
+            migrate::run(nxd, &schemas, &input.json, &input.po, out)?;
+        },
+        CliCommand::Update { nxd, old_nxd, input, out } => {
+            update::run(nxd, &schemas, old_nxd, input, out)?;
+        },
+        CliCommand::Recover { nxd, modded_nxd, out_json, out_po } => {
+            recover::run(nxd, modded_nxd, &schemas, out_json, out_po)?;
+        },
+        CliCommand::Diff { old_nxd, new_nxd, match_by, out_json } => {
+            diff::run(old_nxd, new_nxd, &schemas, *match_by, out_json)?;
         },
-        CliCommand::Import { nxd, input, out } => {
-            import::run(nxd, &input.json, &input.po, out)?;
+        CliCommand::Discover { nxd, out } => {
+            discover::run(nxd, out)?;
         },
     }
     Ok(())
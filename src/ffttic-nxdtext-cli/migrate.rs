@@ -0,0 +1,91 @@
+// Copyright (C) 2025  Mátyás Mustoha
+
+use crate::{Error, path_to_tablename};
+use ffttic_nxdtext_core as nxd;
+use polib::message::{MessageMutView, MessageView};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+
+fn migrate_json(in_path: &Path, out_path: &Path, keys: &HashMap<String, String>) -> Result<(), Error> {
+    let file = File::open(in_path)?;
+    let map: serde_json::Map<String, serde_json::Value> = serde_json::from_reader(file)?;
+
+    let mut new_map = serde_json::Map::with_capacity(map.len());
+    for (key, val) in map {
+        let new_key = match keys.get(&key) {
+            Some(new_key) => new_key.clone(),
+            None => {
+                eprintln!("Warning: Unknown key `{}`, kept as is", key);
+                key
+            },
+        };
+        new_map.insert(new_key, val);
+    }
+
+    let json_content = serde_json::to_string_pretty(&new_map)?;
+
+    let mut json_file = File::create(out_path)?;
+    json_file.write_all(json_content.as_bytes())?;
+    Ok(())
+}
+
+
+fn migrate_po(in_path: &Path, out_path: &Path, keys: &HashMap<String, String>) -> Result<(), Error> {
+    let po_options = polib::po_file::POParseOptions {
+        message_body_only: false,
+        translated_only: false,
+        unsafe_utf8_decode: false,
+    };
+    let mut catalog = polib::po_file::parse_with_option(in_path, &po_options)?;
+
+    for mut message in catalog.messages_mut() {
+        let key = message.msgctxt().unwrap_or_default().to_string();
+        let new_key = match keys.get(&key) {
+            Some(new_key) => new_key.clone(),
+            None => {
+                eprintln!("Warning: Unknown key `{}`, kept as is", key);
+                continue;
+            },
+        };
+        message.set_msgctxt(new_key);
+    }
+
+    let file = File::create(out_path)?;
+    let mut writer = BufWriter::new(file);
+    polib::po_file::write(&catalog, &mut writer)?;
+    Ok(())
+}
+
+
+pub fn run(
+    nxd_path: &Path,
+    schemas: &nxd::Schemas,
+    in_json: &Option<PathBuf>,
+    in_po: &Option<PathBuf>,
+    out_path: &Path,
+) -> Result<(), Error> {
+    let tablename = path_to_tablename(nxd_path)?;
+
+    let nxdfile = File::open(nxd_path)?;
+    let mut reader = BufReader::new(nxdfile);
+    let keys = nxd::read_key_migration(&mut reader, tablename, schemas)?
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+
+    if let Some(parent) = out_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if let Some(json_path) = in_json {
+        migrate_json(json_path, out_path, &keys)?;
+    }
+    if let Some(po_path) = in_po {
+        migrate_po(po_path, out_path, &keys)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,29 @@
+// Copyright (C) 2025  Mátyás Mustoha
+
+use crate::{Error, path_to_tablename};
+use ffttic_nxdtext_core as nxd;
+use std::{
+    fs::{self, File},
+    io::{BufReader, Seek, Write},
+    path::Path,
+};
+
+
+pub fn run(nxd_path: &Path, schemas: &nxd::Schemas, out_nxd: &Path) -> Result<(), Error> {
+    let tablename = path_to_tablename(nxd_path)?;
+
+    let nxdfile = File::open(nxd_path)?;
+    let mut reader = BufReader::new(nxdfile);
+    nxd::check_regions(&mut reader, tablename, schemas)?;
+    reader.rewind()?;
+    let table = nxd::read_table(&mut reader, tablename, schemas)?;
+    let out_buf = nxd::write_table(&table)?;
+
+    if let Some(parent) = out_nxd.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut out_file = File::create(out_nxd)?;
+    out_file.write_all(&out_buf)?;
+
+    Ok(())
+}
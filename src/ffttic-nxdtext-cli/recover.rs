@@ -0,0 +1,84 @@
+// Copyright (C) 2025  Mátyás Mustoha
+
+use crate::{Error, diff::TableDiff, export, path_to_tablename};
+use ffttic_nxdtext_core as nxd;
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+
+/// Reads the rows of a table. The modded files may be named differently, so the name of
+/// the table is taken from the original one.
+fn read_table_rows(
+    nxd_path: &Path,
+    tablename: &str,
+    schemas: &nxd::Schemas,
+) -> Result<Vec<(String, String)>, Error> {
+    let nxdfile = File::open(nxd_path)?;
+    let mut reader = BufReader::new(nxdfile);
+    Ok(nxd::read_rows(&mut reader, tablename, schemas)?)
+}
+
+
+/// Writes the texts the mod changed as a translation of the original ones. Texts the mod
+/// left as they were stay untranslated.
+pub fn run(
+    nxd_path: &Path,
+    modded_nxd_path: &Path,
+    schemas: &nxd::Schemas,
+    out_json: &Option<PathBuf>,
+    out_po: &Option<PathBuf>,
+) -> Result<(), Error> {
+    let tablename = path_to_tablename(nxd_path)?;
+    let rows = read_table_rows(nxd_path, tablename, schemas)?;
+    let modded_rows = read_table_rows(modded_nxd_path, tablename, schemas)?;
+    let diff = TableDiff::new(rows.clone(), modded_rows);
+
+    for (key, _) in &diff.removed {
+        eprintln!("Warning: `{}` is missing from the modded table", key);
+    }
+
+    if let Some(json_path) = out_json {
+        if let Some(parent) = json_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let modded_texts = diff
+            .changed
+            .iter()
+            .map(|(key, _, modded_text)| (key.clone(), modded_text.clone()))
+            .chain(diff.added.iter().cloned())
+            .collect::<Vec<_>>();
+        export::save_json(&modded_texts, json_path)?;
+        if !diff.added.is_empty() {
+            eprintln!(
+                "Note: The mod added {} text(s) in new rows, import them with `--add-rows`",
+                diff.added.len()
+            );
+        }
+    }
+    if let Some(po_path) = out_po {
+        if let Some(parent) = po_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let modded_texts = diff
+            .changed
+            .iter()
+            .map(|(key, _, modded_text)| (key.clone(), modded_text.clone()))
+            .collect::<HashMap<_, _>>();
+        export::save_bilingual_po(&rows, &modded_texts, po_path)?;
+        for (key, _) in &diff.added {
+            eprintln!("Warning: `{}` has no original text, and was skipped", key);
+        }
+    }
+
+    println!(
+        "The mod changed {} text(s), added {} and left {} as they were",
+        diff.changed.len(),
+        diff.added.len(),
+        diff.unchanged
+    );
+    Ok(())
+}
@@ -0,0 +1,97 @@
+// Copyright (C) 2025  Mátyás Mustoha
+
+use crate::{Error, split_translation_key};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+
+pub const KEY_HEADER: &str = "key";
+pub const SOURCE_HEADER: &str = "source";
+pub const TARGET_HEADER: &str = "target";
+
+
+/// Finds a column by its name in the header row, ignoring the case.
+pub fn find_column<'a>(
+    headers: impl IntoIterator<Item = &'a str>,
+    name: &str,
+) -> Result<usize, Error> {
+    headers
+        .into_iter()
+        .position(|header| header.trim().eq_ignore_ascii_case(name))
+        .ok_or_else(|| Error(format!("The file has no `{}` column", name)))
+}
+
+
+/// Writes the texts of a table as CSV or TSV, one row per text. The target column is
+/// only filled when the texts of a target table are known.
+pub fn save_table(
+    rows: &[(String, String)],
+    target_texts: Option<&HashMap<String, String>>,
+    with_context: bool,
+    delimiter: u8,
+    out_path: &Path,
+) -> Result<(), Error> {
+    let mut file = BufWriter::new(File::create(out_path)?);
+    // Spreadsheet applications only detect UTF-8 with a byte order mark
+    file.write_all("\u{feff}".as_bytes())?;
+
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(file);
+
+    let mut header = vec![KEY_HEADER];
+    if with_context {
+        header.extend(["table", "row", "column"]);
+    }
+    header.extend([SOURCE_HEADER, TARGET_HEADER]);
+    writer.write_record(&header)?;
+
+    for (key, text) in rows {
+        let target_text = target_texts
+            .and_then(|texts| texts.get(key))
+            .map_or("", String::as_str);
+
+        let mut record = vec![key.as_str()];
+        if with_context {
+            let (table, row, column) = split_translation_key(key);
+            record.extend([table, row, column]);
+        }
+        record.extend([text.as_str(), target_text]);
+        writer.write_record(&record)?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+
+/// Reads the translations of a CSV or TSV file, found by the names of its columns.
+/// Every other column is ignored, and so are the rows without a translation.
+pub fn load_table(
+    path: &Path,
+    delimiter: u8,
+    overrides: &mut HashMap<String, String>,
+) -> Result<(), Error> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_path(path)?;
+
+    let headers = reader.headers()?;
+    let key_idx = find_column(headers, KEY_HEADER)?;
+    let target_idx = find_column(headers, TARGET_HEADER)?;
+
+    for record in reader.records() {
+        let record = record?;
+        let key = record.get(key_idx).unwrap_or_default();
+        let target_text = record.get(target_idx).unwrap_or_default();
+        if !key.is_empty() && !target_text.is_empty() {
+            overrides.insert(key.to_string(), target_text.to_string());
+        }
+    }
+    Ok(())
+}
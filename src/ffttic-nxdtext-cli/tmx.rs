@@ -0,0 +1,103 @@
+// Copyright (C) 2025  Mátyás Mustoha
+
+use crate::{Error, cli::CliFormatOptions, split_translation_key};
+use quick_xml::{
+    Writer,
+    events::{BytesDecl, BytesText, Event},
+};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+
+fn write_unit(
+    writer: &mut Writer<impl Write>,
+    key: &str,
+    langs: [&str; 2],
+    texts: [&str; 2],
+) -> io::Result<()> {
+    let (table, row, column) = split_translation_key(key);
+    writer
+        .create_element("tu")
+        .with_attribute(("tuid", key))
+        .write_inner_content(|writer| {
+            for (kind, value) in [("x-table", table), ("x-row", row), ("x-column", column)] {
+                writer
+                    .create_element("prop")
+                    .with_attribute(("type", kind))
+                    .write_text_content(BytesText::new(value))?;
+            }
+            for (lang, text) in langs.into_iter().zip(texts) {
+                writer
+                    .create_element("tuv")
+                    .with_attribute(("xml:lang", lang))
+                    .write_inner_content(|writer| {
+                        writer
+                            .create_element("seg")
+                            .write_text_content(BytesText::new(text))?;
+                        Ok(())
+                    })?;
+            }
+            Ok(())
+        })?;
+    Ok(())
+}
+
+
+/// Writes a TMX 1.4 translation memory, with a translation unit for every text that has
+/// a translation. The table, row key and column of the text are stored as properties.
+pub fn save_tmx(
+    rows: &[(String, String)],
+    target_texts: &HashMap<String, String>,
+    options: &CliFormatOptions,
+    out_path: &Path,
+) -> Result<(), Error> {
+    let source_lang = options.source_lang.as_str();
+    let target_lang = options.target_lang.as_deref().ok_or_else(|| {
+        Error(
+            "TMX files need the language of the translations, given with `--target-lang`"
+                .to_owned(),
+        )
+    })?;
+
+    let file = File::create(out_path)?;
+    let mut writer = Writer::new_with_indent(BufWriter::new(file), b' ', 2);
+    writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+
+    let units = |writer: &mut Writer<_>| -> io::Result<()> {
+        for (key, text) in rows {
+            if let Some(target_text) = target_texts.get(key) {
+                write_unit(writer, key, [source_lang, target_lang], [text, target_text])?;
+            }
+        }
+        Ok(())
+    };
+
+    writer
+        .create_element("tmx")
+        .with_attribute(("version", "1.4"))
+        .write_inner_content(|writer| {
+            writer
+                .create_element("header")
+                .with_attributes([
+                    ("creationtool", "ffttic-nxdtext"),
+                    ("creationtoolversion", env!("CARGO_PKG_VERSION")),
+                    ("segtype", "block"),
+                    ("o-tmf", "nxd"),
+                    ("adminlang", "en"),
+                    ("srclang", source_lang),
+                    ("datatype", "plaintext"),
+                ])
+                .write_empty()?;
+            writer.create_element("body").write_inner_content(units)?;
+            Ok(())
+        })?;
+
+    let mut writer = writer.into_inner();
+    writer.write_all(b"\n")?;
+    writer.flush()?;
+    Ok(())
+}
@@ -0,0 +1,332 @@
+// Copyright (C) 2025  Mátyás Mustoha
+
+use crate::{Error, cli::CliTranslationInput, read_nxd_rows};
+use ffttic_nxdtext_core as nxd;
+use polib::message::Message;
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+
+fn escape_po(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace('\t', "\\t")
+}
+
+
+/// Writes a field of a commented out entry, like `#| msgid "..."`, split at the line breaks.
+fn write_commented_field(out: &mut String, prefix: &str, name: &str, text: &str) {
+    let escaped = escape_po(text);
+    let lines = escaped.split_inclusive("\\n").collect::<Vec<_>>();
+    if lines.len() <= 1 {
+        out.push_str(&format!("{} {} \"{}\"\n", prefix, name, escaped));
+    } else {
+        out.push_str(&format!("{} {} \"\"\n", prefix, name));
+        for line in lines {
+            out.push_str(&format!("{} \"{}\"\n", prefix, line));
+        }
+    }
+}
+
+
+/// Keeps the translations whose source text didn't change. The changed ones are marked
+/// fuzzy along with their previous source text, and the removed ones are made obsolete.
+fn update_po(
+    in_path: &Path,
+    out_path: &Path,
+    rows: &[(String, String)],
+    old_sources: Option<&HashMap<String, String>>,
+) -> Result<(), Error> {
+    let po_options = polib::po_file::POParseOptions {
+        message_body_only: false,
+        translated_only: false,
+        unsafe_utf8_decode: false,
+    };
+    let mut old_catalog = polib::po_file::parse_with_option(in_path, &po_options)?;
+    let mut catalog = polib::catalog::Catalog::new(std::mem::take(&mut old_catalog.metadata));
+
+    let mut old_messages = HashMap::new();
+    let mut old_keys = Vec::new();
+    for message in old_catalog.messages() {
+        let key = message.msgctxt().unwrap_or_default().to_string();
+        old_keys.push(key.clone());
+        old_messages.insert(key, message);
+    }
+
+    let mut previous_sources = HashMap::new();
+
+    for (key, text) in rows {
+        let mut builder = Message::build_singular();
+        builder.with_msgctxt(key.clone()).with_msgid(text.clone());
+
+        if let Some(old_message) = old_messages.get(key) {
+            let old_source = old_sources
+                .and_then(|sources| sources.get(key))
+                .map_or(old_message.msgid(), String::as_str);
+            let translation = old_message.msgstr().unwrap_or_default();
+            let mut flags = old_message.flags().clone();
+            if old_source != text && !translation.is_empty() {
+                flags.add_flag("fuzzy");
+                previous_sources.insert(key.as_str(), old_source.to_string());
+            }
+            builder
+                .with_comments(old_message.comments().to_string())
+                .with_source(old_message.source().to_string())
+                .with_flags(flags)
+                .with_msgstr(translation.to_string());
+        }
+        catalog.append_or_update(builder.done());
+    }
+
+    let new_keys = rows
+        .iter()
+        .map(|(key, _)| key.as_str())
+        .collect::<HashSet<_>>();
+    let obsolete = old_keys
+        .iter()
+        .filter(|key| !new_keys.contains(key.as_str()))
+        .map(|key| {
+            let old_message = &old_messages[key];
+            let translation = old_message.msgstr().unwrap_or_default();
+            (key.as_str(), old_message.msgid(), translation)
+        })
+        .collect::<Vec<_>>();
+
+    let mut po_content = Vec::new();
+    polib::po_file::write(&catalog, &mut po_content)?;
+    let po_content = String::from_utf8(po_content)?;
+    let out = add_po_history(&po_content, &previous_sources, &obsolete);
+
+    fs::write(out_path, out)?;
+    Ok(())
+}
+
+
+/// Adds what polib can't write on its own to a PO file written by it: the previous source
+/// texts of the entries, before their `msgctxt`, and the obsolete entries (with their key,
+/// source and translation) at the end.
+fn add_po_history(
+    po_content: &str,
+    previous_sources: &HashMap<&str, String>,
+    obsolete: &[(&str, &str, &str)],
+) -> String {
+    let previous_sources = previous_sources
+        .iter()
+        .map(|(key, text)| (escape_po(key), text))
+        .collect::<HashMap<_, _>>();
+
+    let mut out = String::with_capacity(po_content.len());
+    for line in po_content.split_inclusive('\n') {
+        let previous_source = line
+            .strip_prefix("msgctxt \"")
+            .and_then(|rest| rest.trim_end().strip_suffix('"'))
+            .and_then(|key| previous_sources.get(key));
+        if let Some(previous_source) = previous_source {
+            write_commented_field(&mut out, "#|", "msgid", previous_source);
+        }
+        out.push_str(line);
+    }
+
+    for (key, source, translation) in obsolete {
+        write_commented_field(&mut out, "#~", "msgctxt", key);
+        write_commented_field(&mut out, "#~", "msgid", source);
+        write_commented_field(&mut out, "#~", "msgstr", translation);
+        out.push('\n');
+    }
+    out
+}
+
+
+/// Flattens nested objects, like the sets of an export grouped by set.
+fn flatten_json(
+    map: serde_json::Map<String, serde_json::Value>,
+    out: &mut serde_json::Map<String, serde_json::Value>,
+) {
+    for (key, value) in map {
+        match value {
+            serde_json::Value::Object(inner_map) => flatten_json(inner_map, out),
+            value => {
+                out.insert(key, value);
+            },
+        }
+    }
+}
+
+
+/// Keeps every translation, and adds the new keys with their source text. As JSON can't
+/// mark entries, the ones whose source text changed and the removed ones are reported.
+/// Files grouped by set are written without the grouping.
+fn update_json(
+    in_path: &Path,
+    out_path: &Path,
+    rows: &[(String, String)],
+    old_sources: &HashMap<String, String>,
+) -> Result<(), Error> {
+    let file = File::open(in_path)?;
+    let nested_map: serde_json::Map<String, serde_json::Value> = serde_json::from_reader(file)?;
+    let mut map = serde_json::Map::with_capacity(nested_map.len());
+    flatten_json(nested_map, &mut map);
+
+    let mut new_map = serde_json::Map::with_capacity(rows.len());
+    for (key, text) in rows {
+        let value = match map.remove(key) {
+            Some(value) => {
+                if old_sources
+                    .get(key)
+                    .is_some_and(|old_source| old_source != text)
+                {
+                    eprintln!("Warning: The source text of `{}` has changed", key);
+                }
+                value
+            },
+            None => serde_json::Value::String(text.clone()),
+        };
+        new_map.insert(key.clone(), value);
+    }
+
+    // Numbers are not part of the text, so they are kept as they are
+    for (key, value) in map {
+        match value {
+            serde_json::Value::String(_) => {
+                eprintln!("Warning: `{}` was removed from the table", key);
+            },
+            _ => {
+                new_map.insert(key, value);
+            },
+        }
+    }
+
+    let json_content = serde_json::to_string_pretty(&new_map)?;
+
+    let mut json_file = File::create(out_path)?;
+    json_file.write_all(json_content.as_bytes())?;
+    Ok(())
+}
+
+
+pub fn run(
+    nxd_path: &Path,
+    schemas: &nxd::Schemas,
+    old_nxd_path: &Option<PathBuf>,
+    input: &CliTranslationInput,
+    out_path: &Path,
+) -> Result<(), Error> {
+    let rows = read_nxd_rows(nxd_path, schemas)?;
+    let old_sources = match old_nxd_path {
+        Some(old_nxd_path) => {
+            let old_rows = read_nxd_rows(old_nxd_path, schemas)?;
+            Some(old_rows.into_iter().collect::<HashMap<_, _>>())
+        },
+        None => None,
+    };
+
+    if let Some(parent) = out_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if let Some(json_path) = &input.json {
+        let old_sources = old_sources.as_ref().ok_or_else(|| {
+            Error("Updating a JSON file needs the original NXD file too".to_owned())
+        })?;
+        update_json(json_path, out_path, &rows, old_sources)?;
+    }
+    if let Some(po_path) = &input.po {
+        update_po(po_path, out_path, &rows, old_sources.as_ref())?;
+    }
+
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    fn rows(texts: &[(&str, &str)]) -> Vec<(String, String)> {
+        texts
+            .iter()
+            .map(|(key, text)| (key.to_string(), text.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn po_history() {
+        let mut catalog = polib::catalog::Catalog::new(Default::default());
+        for (key, text) in [("charaname/10/2", "Ramza"), ("charaname/11/2", "Delita")] {
+            let mut builder = Message::build_singular();
+            builder.with_msgctxt(key.to_owned()).with_msgid(text.to_owned());
+            catalog.append_or_update(builder.done());
+        }
+        let mut po_content = Vec::new();
+        polib::po_file::write(&catalog, &mut po_content).unwrap();
+        let po_content = String::from_utf8(po_content).unwrap();
+
+        let previous_source = "Old \"Delita\"\nline".to_owned();
+        let previous_sources = HashMap::from([("charaname/11/2", previous_source)]);
+        let obsolete = [("charaname/15/2", "Ramza", "Ramsa")];
+        let out = add_po_history(&po_content, &previous_sources, &obsolete);
+
+        let expected_previous = concat!(
+            r#"#| msgid """#,
+            "\n",
+            r#"#| "Old \"Delita\"\n""#,
+            "\n",
+            r#"#| "line""#,
+            "\n",
+            r#"msgctxt "charaname/11/2""#,
+        );
+        assert!(out.contains(expected_previous), "{}", out);
+        assert_eq!(out.matches("#|").count(), 3);
+        let expected_obsolete = concat!(
+            r#"#~ msgctxt "charaname/15/2""#,
+            "\n",
+            r#"#~ msgid "Ramza""#,
+            "\n",
+            r#"#~ msgstr "Ramsa""#,
+            "\n\n",
+        );
+        assert!(out.ends_with(expected_obsolete), "{}", out);
+        assert_eq!(add_po_history(&po_content, &HashMap::new(), &[]), po_content);
+    }
+
+    #[test]
+    fn update_grouped_json() {
+        let dir = env::temp_dir().join(format!("nxdtext-update-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (in_path, out_path) = (dir.join("in.json"), dir.join("out.json"));
+        let grouped = r#"{
+            "uisubtitles/30": { "uisubtitles/30/1/2": "Salut", "uisubtitles/30/1/4": 3 },
+            "uisubtitles/31": {
+                "uisubtitles/31/1/2": "Troisième",
+                "uisubtitles/31/2/2": "Removed"
+            }
+        }"#;
+        fs::write(&in_path, grouped).unwrap();
+
+        let new_rows = rows(&[
+            ("uisubtitles/30/1/2", "Hello"),
+            ("uisubtitles/31/1/2", "Third"),
+            ("uisubtitles/32/1/2", "New"),
+        ]);
+        let old_sources = rows(&[("uisubtitles/30/1/2", "Hello"), ("uisubtitles/31/1/2", "Third")]);
+        let old_sources = old_sources.into_iter().collect::<HashMap<_, _>>();
+        update_json(&in_path, &out_path, &new_rows, &old_sources).unwrap();
+
+        let out_map: serde_json::Map<String, serde_json::Value> =
+            serde_json::from_str(&fs::read_to_string(&out_path).unwrap()).unwrap();
+        let expected = serde_json::json!({
+            "uisubtitles/30/1/2": "Salut",
+            "uisubtitles/31/1/2": "Troisième",
+            "uisubtitles/32/1/2": "New",
+            "uisubtitles/30/1/4": 3,
+        });
+        assert_eq!(serde_json::Value::Object(out_map), expected);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
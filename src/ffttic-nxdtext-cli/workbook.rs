@@ -0,0 +1,446 @@
+// Copyright (C) 2025  Mátyás Mustoha
+
+use crate::{
+    Error,
+    spreadsheet::{KEY_HEADER, SOURCE_HEADER, TARGET_HEADER, find_column},
+};
+use calamine::Reader as _;
+use quick_xml::{
+    Writer,
+    events::{BytesDecl, BytesStart, BytesText, Event},
+};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, Write},
+    path::Path,
+};
+
+
+const COMMENT_HEADER: &str = "comment";
+
+/// The columns of every sheet, with their width in characters.
+const COLUMNS: [(&str, f64); 4] = [
+    (KEY_HEADER, 24.0),
+    (SOURCE_HEADER, 60.0),
+    (TARGET_HEADER, 60.0),
+    (COMMENT_HEADER, 40.0),
+];
+
+
+/// The texts of a table, written to a sheet named after it.
+pub struct Sheet<'a> {
+    pub tablename: &'a str,
+    pub rows: &'a [(String, String)],
+    pub target_texts: Option<&'a HashMap<String, String>>,
+}
+
+impl Sheet<'_> {
+    /// The cells of every row, in the order of the columns.
+    fn records(&self) -> impl Iterator<Item = [&str; 4]> {
+        self.rows.iter().map(|(key, text)| {
+            let target_text = self
+                .target_texts
+                .and_then(|texts| texts.get(key))
+                .map_or("", String::as_str);
+            [key.as_str(), text.as_str(), target_text, ""]
+        })
+    }
+}
+
+
+/// Writes an Excel workbook, with a frozen header row and wrapped text cells.
+pub fn save_xlsx(sheets: &[Sheet], out_path: &Path) -> Result<(), Error> {
+    let header_format = rust_xlsxwriter::Format::new()
+        .set_bold()
+        .set_align(rust_xlsxwriter::FormatAlign::Top);
+    let text_format = rust_xlsxwriter::Format::new()
+        .set_text_wrap()
+        .set_align(rust_xlsxwriter::FormatAlign::Top);
+
+    let mut workbook = rust_xlsxwriter::Workbook::new();
+    for sheet in sheets {
+        let worksheet = workbook.add_worksheet();
+        worksheet.set_name(sheet.tablename)?;
+        worksheet.set_freeze_panes(1, 0)?;
+
+        for (col, (header, width)) in (0..).zip(COLUMNS) {
+            worksheet.set_column_width(col, width)?;
+            worksheet.write_string_with_format(0, col, header, &header_format)?;
+        }
+        for (row, record) in (1..).zip(sheet.records()) {
+            for (col, text) in (0..).zip(record) {
+                worksheet.write_string_with_format(row, col, text, &text_format)?;
+            }
+        }
+    }
+
+    workbook.save(out_path)?;
+    Ok(())
+}
+
+
+/// Writes a text as paragraphs, keeping the tabs and the runs of spaces that ODF would
+/// otherwise collapse.
+fn write_ods_text(writer: &mut Writer<impl Write>, text: &str) -> io::Result<()> {
+    for line in text.split('\n') {
+        writer.write_event(Event::Start(BytesStart::new("text:p")))?;
+
+        let mut chars = line.chars().peekable();
+        let mut plain = String::new();
+        while let Some(ch) = chars.next() {
+            let is_collapsed_space = ch == ' ' && (plain.is_empty() || plain.ends_with(' '));
+            if ch != '\t' && !is_collapsed_space {
+                plain.push(ch);
+                continue;
+            }
+            writer.write_event(Event::Text(BytesText::new(&plain)))?;
+            plain.clear();
+
+            if ch == '\t' {
+                writer.write_event(Event::Empty(BytesStart::new("text:tab")))?;
+            } else {
+                let mut count = 1;
+                while chars.next_if_eq(&' ').is_some() {
+                    count += 1;
+                }
+                let count = count.to_string();
+                let space = BytesStart::new("text:s").with_attributes([("text:c", count.as_str())]);
+                writer.write_event(Event::Empty(space))?;
+            }
+        }
+        writer.write_event(Event::Text(BytesText::new(&plain)))?;
+
+        writer.write_event(Event::End(BytesStart::new("text:p").to_end()))?;
+    }
+    Ok(())
+}
+
+
+fn write_ods_content(writer: &mut Writer<impl Write>, sheets: &[Sheet]) -> io::Result<()> {
+    let styles = |writer: &mut Writer<_>| -> io::Result<()> {
+        for (idx, (_, width)) in COLUMNS.iter().enumerate() {
+            let width = format!("{:.1}cm", width * 0.2);
+            writer
+                .create_element("style:style")
+                .with_attributes([
+                    ("style:name", format!("co{}", idx + 1).as_str()),
+                    ("style:family", "table-column"),
+                ])
+                .write_inner_content(|writer| {
+                    writer
+                        .create_element("style:table-column-properties")
+                        .with_attribute(("style:column-width", width.as_str()))
+                        .write_empty()?;
+                    Ok(())
+                })?;
+        }
+        for (name, is_bold) in [("header", true), ("text", false)] {
+            writer
+                .create_element("style:style")
+                .with_attributes([("style:name", name), ("style:family", "table-cell")])
+                .write_inner_content(|writer| {
+                    writer
+                        .create_element("style:table-cell-properties")
+                        .with_attributes([
+                            ("fo:wrap-option", "wrap"),
+                            ("style:vertical-align", "top"),
+                        ])
+                        .write_empty()?;
+                    if is_bold {
+                        writer
+                            .create_element("style:text-properties")
+                            .with_attribute(("fo:font-weight", "bold"))
+                            .write_empty()?;
+                    }
+                    Ok(())
+                })?;
+        }
+        Ok(())
+    };
+
+    let write_row = |writer: &mut Writer<_>, record: [&str; 4], style: &str| -> io::Result<()> {
+        writer
+            .create_element("table:table-row")
+            .write_inner_content(|writer| {
+                for text in record {
+                    let mut cell = writer
+                        .create_element("table:table-cell")
+                        .with_attribute(("table:style-name", style));
+                    if text.is_empty() {
+                        cell.write_empty()?;
+                        continue;
+                    }
+                    cell = cell.with_attribute(("office:value-type", "string"));
+                    // Some readers drop the tabs of the paragraphs, but not of the value
+                    if text.contains('\t') {
+                        cell = cell.with_attribute(("office:string-value", text));
+                    }
+                    cell.write_inner_content(|writer| write_ods_text(writer, text))?;
+                }
+                Ok(())
+            })?;
+        Ok(())
+    };
+
+    let tables = |writer: &mut Writer<_>| -> io::Result<()> {
+        for sheet in sheets {
+            writer
+                .create_element("table:table")
+                .with_attribute(("table:name", sheet.tablename))
+                .write_inner_content(|writer| {
+                    for idx in 0..COLUMNS.len() {
+                        writer
+                            .create_element("table:table-column")
+                            .with_attribute(("table:style-name", format!("co{}", idx + 1).as_str()))
+                            .write_empty()?;
+                    }
+                    write_row(writer, COLUMNS.map(|(header, _)| header), "header")?;
+                    for record in sheet.records() {
+                        write_row(writer, record, "text")?;
+                    }
+                    Ok(())
+                })?;
+        }
+        Ok(())
+    };
+
+    writer
+        .create_element("office:document-content")
+        .with_attributes([
+            (
+                "xmlns:office",
+                "urn:oasis:names:tc:opendocument:xmlns:office:1.0",
+            ),
+            (
+                "xmlns:style",
+                "urn:oasis:names:tc:opendocument:xmlns:style:1.0",
+            ),
+            (
+                "xmlns:text",
+                "urn:oasis:names:tc:opendocument:xmlns:text:1.0",
+            ),
+            (
+                "xmlns:table",
+                "urn:oasis:names:tc:opendocument:xmlns:table:1.0",
+            ),
+            (
+                "xmlns:fo",
+                "urn:oasis:names:tc:opendocument:xmlns:xsl-fo-compatible:1.0",
+            ),
+            ("office:version", "1.2"),
+        ])
+        .write_inner_content(|writer| {
+            writer
+                .create_element("office:automatic-styles")
+                .write_inner_content(styles)?;
+            writer
+                .create_element("office:body")
+                .write_inner_content(|writer| {
+                    writer
+                        .create_element("office:spreadsheet")
+                        .write_inner_content(tables)?;
+                    Ok(())
+                })?;
+            Ok(())
+        })?;
+    Ok(())
+}
+
+
+/// Freezes the header row of every sheet, the way LibreOffice stores it.
+fn write_ods_settings(writer: &mut Writer<impl Write>, sheets: &[Sheet]) -> io::Result<()> {
+    let write_item = |writer: &mut Writer<_>, name: &str, kind: &str, value: &str| {
+        writer
+            .create_element("config:config-item")
+            .with_attributes([("config:name", name), ("config:type", kind)])
+            .write_text_content(BytesText::new(value))?;
+        io::Result::Ok(())
+    };
+
+    let tables = |writer: &mut Writer<_>| -> io::Result<()> {
+        for sheet in sheets {
+            writer
+                .create_element("config:config-item-map-entry")
+                .with_attribute(("config:name", sheet.tablename))
+                .write_inner_content(|writer| {
+                    write_item(writer, "VerticalSplitMode", "short", "2")?;
+                    write_item(writer, "VerticalSplitPosition", "int", "1")?;
+                    write_item(writer, "ActiveSplitRange", "short", "2")?;
+                    write_item(writer, "PositionBottom", "int", "1")
+                })?;
+        }
+        Ok(())
+    };
+
+    writer
+        .create_element("office:document-settings")
+        .with_attributes([
+            (
+                "xmlns:office",
+                "urn:oasis:names:tc:opendocument:xmlns:office:1.0",
+            ),
+            (
+                "xmlns:config",
+                "urn:oasis:names:tc:opendocument:xmlns:config:1.0",
+            ),
+            ("office:version", "1.2"),
+        ])
+        .write_inner_content(|writer| {
+            writer
+                .create_element("office:settings")
+                .write_inner_content(|writer| {
+                    writer
+                        .create_element("config:config-item-set")
+                        .with_attribute(("config:name", "ooo:view-settings"))
+                        .write_inner_content(|writer| {
+                            writer
+                                .create_element("config:config-item-map-indexed")
+                                .with_attribute(("config:name", "Views"))
+                                .write_inner_content(|writer| {
+                                    writer
+                                        .create_element("config:config-item-map-entry")
+                                        .write_inner_content(|writer| {
+                                            write_item(writer, "ViewId", "string", "view1")?;
+                                            writer
+                                                .create_element("config:config-item-map-named")
+                                                .with_attribute(("config:name", "Tables"))
+                                                .write_inner_content(tables)?;
+                                            Ok(())
+                                        })?;
+                                    Ok(())
+                                })?;
+                            Ok(())
+                        })?;
+                    Ok(())
+                })?;
+            Ok(())
+        })?;
+    Ok(())
+}
+
+
+fn write_ods_manifest(writer: &mut Writer<impl Write>) -> io::Result<()> {
+    let entries = [
+        ("/", "application/vnd.oasis.opendocument.spreadsheet"),
+        ("content.xml", "text/xml"),
+        ("settings.xml", "text/xml"),
+    ];
+    writer
+        .create_element("manifest:manifest")
+        .with_attributes([
+            (
+                "xmlns:manifest",
+                "urn:oasis:names:tc:opendocument:xmlns:manifest:1.0",
+            ),
+            ("manifest:version", "1.2"),
+        ])
+        .write_inner_content(|writer| {
+            for (path, media_type) in entries {
+                let element = writer
+                    .create_element("manifest:file-entry")
+                    .with_attributes([
+                        ("manifest:full-path", path),
+                        ("manifest:media-type", media_type),
+                    ]);
+                match path {
+                    "/" => element.with_attribute(("manifest:version", "1.2")),
+                    _ => element,
+                }
+                .write_empty()?;
+            }
+            Ok(())
+        })?;
+    Ok(())
+}
+
+
+fn xml_document(
+    write_root: impl FnOnce(&mut Writer<Vec<u8>>) -> io::Result<()>,
+) -> io::Result<Vec<u8>> {
+    let mut writer = Writer::new(Vec::new());
+    writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+    write_root(&mut writer)?;
+    Ok(writer.into_inner())
+}
+
+
+/// Writes an OpenDocument spreadsheet, with a frozen header row and wrapped text cells.
+pub fn save_ods(sheets: &[Sheet], out_path: &Path) -> Result<(), Error> {
+    let parts = [
+        ("META-INF/manifest.xml", xml_document(write_ods_manifest)?),
+        (
+            "content.xml",
+            xml_document(|writer| write_ods_content(writer, sheets))?,
+        ),
+        (
+            "settings.xml",
+            xml_document(|writer| write_ods_settings(writer, sheets))?,
+        ),
+    ];
+
+    let mut zip = zip::ZipWriter::new(File::create(out_path)?);
+    // The MIME type has to come first and uncompressed, so the format can be detected
+    let stored =
+        zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    zip.start_file("mimetype", stored)?;
+    zip.write_all(b"application/vnd.oasis.opendocument.spreadsheet")?;
+
+    for (name, content) in parts {
+        zip.start_file(name, zip::write::SimpleFileOptions::default())?;
+        zip.write_all(&content)?;
+    }
+
+    zip.finish()?;
+    Ok(())
+}
+
+
+/// Lists the sheets of an XLSX or ODS workbook.
+pub fn sheet_names(path: &Path) -> Result<Vec<String>, Error> {
+    let workbook = calamine::open_workbook_auto(path)?;
+    Ok(workbook.sheet_names())
+}
+
+
+/// Reads the translations of a table from the sheet named after it, found by the names
+/// of its columns. Comments and every other column are ignored, and so are the rows
+/// without a translation.
+pub fn load_workbook(
+    path: &Path,
+    tablename: &str,
+    overrides: &mut HashMap<String, String>,
+) -> Result<(), Error> {
+    let mut workbook = calamine::open_workbook_auto(path)?;
+    if !workbook.sheet_names().iter().any(|name| name == tablename) {
+        return Err(Error(format!(
+            "The workbook has no sheet for the table `{}`",
+            tablename
+        )));
+    }
+    let range = workbook.worksheet_range(tablename)?;
+
+    let mut rows = range.rows();
+    let headers = rows
+        .next()
+        .unwrap_or_default()
+        .iter()
+        .map(|cell| cell.to_string())
+        .collect::<Vec<_>>();
+    let key_idx = find_column(headers.iter().map(String::as_str), KEY_HEADER)?;
+    let target_idx = find_column(headers.iter().map(String::as_str), TARGET_HEADER)?;
+
+    for row in rows {
+        let cell_text = |idx: usize| {
+            row.get(idx)
+                .map(|cell| cell.to_string())
+                .unwrap_or_default()
+        };
+        let key = cell_text(key_idx);
+        let target_text = cell_text(target_idx);
+        if !key.is_empty() && !target_text.is_empty() {
+            overrides.insert(key, target_text);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,427 @@
+// Copyright (C) 2025  Mátyás Mustoha
+
+use crate::{
+    Error,
+    cli::{CliFormatOptions, CliXliffVersion},
+    split_translation_key,
+};
+use ffttic_nxdtext_core as nxd;
+use quick_xml::{
+    Reader, Writer,
+    events::{BytesDecl, BytesText, Event},
+};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+
+fn write_text_element(writer: &mut Writer<impl Write>, name: &str, text: &str) -> io::Result<()> {
+    writer
+        .create_element(name)
+        .write_text_content(BytesText::new(text))?;
+    Ok(())
+}
+
+
+/// The name of the column of a text in the table layout, or its index if it has no name.
+fn column_label(schemas: &nxd::Schemas, table: &str, column: &str) -> String {
+    let (col, element) = column.split_at(column.find('[').unwrap_or(column.len()));
+    let name = col
+        .parse::<usize>()
+        .ok()
+        .and_then(|col| schemas.get(table)?.column_name(col));
+    match name {
+        Some(name) => format!("{}{}", name, element),
+        None => column.to_owned(),
+    }
+}
+
+
+/// IDs in XLIFF 2.0 can only have letters, digits and a few punctuation characters, so
+/// every other byte is written as `_` and its hex code, eg. `/` as `_2F`.
+fn to_xliff_id(text: &str) -> String {
+    let mut id = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'.' | b'-' => id.push(byte as char),
+            _ => id += &format!("_{:02X}", byte),
+        }
+    }
+    id
+}
+
+fn from_xliff_id(id: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(id.len());
+    let mut chars = id.bytes();
+    while let Some(byte) = chars.next() {
+        match byte {
+            b'_' => {
+                let hex = [chars.next()?, chars.next()?];
+                bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+            },
+            _ => bytes.push(byte),
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+
+fn write_xliff_1_2(
+    writer: &mut Writer<impl Write>,
+    tablename: &str,
+    rows: &[(String, String)],
+    target_texts: Option<&HashMap<String, String>>,
+    schemas: &nxd::Schemas,
+    options: &CliFormatOptions,
+) -> io::Result<()> {
+    let mut file_attrs = vec![
+        ("original", tablename),
+        ("source-language", options.source_lang.as_str()),
+        ("datatype", "plaintext"),
+    ];
+    if let Some(target_lang) = &options.target_lang {
+        file_attrs.push(("target-language", target_lang.as_str()));
+    }
+
+    let body = |writer: &mut Writer<_>| -> io::Result<()> {
+        for (key, text) in rows {
+            let (table, row, column) = split_translation_key(key);
+            let column = column_label(schemas, table, column);
+            let note = format!("Table: {}, row: {}, column: {}", table, row, column);
+            writer
+                .create_element("trans-unit")
+                .with_attributes([("id", key.as_str()), ("xml:space", "preserve")])
+                .write_inner_content(|writer| {
+                    write_text_element(writer, "source", text)?;
+                    if let Some(target_text) = target_texts.and_then(|texts| texts.get(key)) {
+                        write_text_element(writer, "target", target_text)?;
+                    }
+                    write_text_element(writer, "note", &note)
+                })?;
+        }
+        Ok(())
+    };
+
+    writer
+        .create_element("xliff")
+        .with_attributes([
+            ("version", "1.2"),
+            ("xmlns", "urn:oasis:names:tc:xliff:document:1.2"),
+        ])
+        .write_inner_content(|writer| {
+            writer
+                .create_element("file")
+                .with_attributes(file_attrs)
+                .write_inner_content(|writer| {
+                    writer.create_element("body").write_inner_content(body)?;
+                    Ok(())
+                })?;
+            Ok(())
+        })?;
+    Ok(())
+}
+
+
+fn write_xliff_2_0(
+    writer: &mut Writer<impl Write>,
+    tablename: &str,
+    rows: &[(String, String)],
+    target_texts: Option<&HashMap<String, String>>,
+    schemas: &nxd::Schemas,
+    options: &CliFormatOptions,
+) -> io::Result<()> {
+    let mut xliff_attrs = vec![
+        ("version", "2.0"),
+        ("xmlns", "urn:oasis:names:tc:xliff:document:2.0"),
+        ("srcLang", options.source_lang.as_str()),
+    ];
+    if let Some(target_lang) = &options.target_lang {
+        xliff_attrs.push(("trgLang", target_lang.as_str()));
+    }
+
+    let units = |writer: &mut Writer<_>| -> io::Result<()> {
+        for (key, text) in rows {
+            let (table, row, column) = split_translation_key(key);
+            let column = column_label(schemas, table, column);
+            // The ID has to be encoded, so the key itself is kept as the name of the unit
+            writer
+                .create_element("unit")
+                .with_attributes([("id", to_xliff_id(key).as_str()), ("name", key.as_str())])
+                .write_inner_content(|writer| {
+                    writer
+                        .create_element("notes")
+                        .write_inner_content(|writer| {
+                            let notes = [("table", table), ("row", row), ("column", &column)];
+                            for (category, value) in notes {
+                                writer
+                                    .create_element("note")
+                                    .with_attribute(("category", category))
+                                    .write_text_content(BytesText::new(value))?;
+                            }
+                            Ok(())
+                        })?;
+                    writer
+                        .create_element("segment")
+                        .write_inner_content(|writer| {
+                            write_text_element(writer, "source", text)?;
+                            match target_texts.and_then(|texts| texts.get(key)) {
+                                Some(target_text) => {
+                                    write_text_element(writer, "target", target_text)
+                                },
+                                None => Ok(()),
+                            }
+                        })?;
+                    Ok(())
+                })?;
+        }
+        Ok(())
+    };
+
+    writer
+        .create_element("xliff")
+        .with_attributes(xliff_attrs)
+        .write_inner_content(|writer| {
+            let file_id = to_xliff_id(tablename);
+            writer
+                .create_element("file")
+                .with_attributes([("id", file_id.as_str()), ("xml:space", "preserve")])
+                .write_inner_content(units)?;
+            Ok(())
+        })?;
+    Ok(())
+}
+
+
+/// Writes the texts of a table as translation units, keyed by their translation keys.
+/// The translations are only written when the texts of a target table are known.
+pub fn save_xliff(
+    tablename: &str,
+    rows: &[(String, String)],
+    target_texts: Option<&HashMap<String, String>>,
+    schemas: &nxd::Schemas,
+    options: &CliFormatOptions,
+    out_path: &Path,
+) -> Result<(), Error> {
+    let file = File::create(out_path)?;
+    let mut writer = Writer::new_with_indent(BufWriter::new(file), b' ', 2);
+    writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+
+    match options.xliff_version {
+        CliXliffVersion::V1_2 => {
+            write_xliff_1_2(&mut writer, tablename, rows, target_texts, schemas, options)?
+        },
+        CliXliffVersion::V2_0 if target_texts.is_some() && options.target_lang.is_none() => {
+            return Err(Error(
+                "XLIFF 2.0 files with translations need a target language".to_owned(),
+            ));
+        },
+        CliXliffVersion::V2_0 => {
+            write_xliff_2_0(&mut writer, tablename, rows, target_texts, schemas, options)?
+        },
+    }
+
+    let mut writer = writer.into_inner();
+    writer.write_all(b"\n")?;
+    writer.flush()?;
+    Ok(())
+}
+
+
+/// Reads the translations of an XLIFF 1.2 or 2.0 file. Units without a translation are
+/// skipped, and so are the alternative translations some tools add. The key of an XLIFF 2.0
+/// unit is its name, or its decoded ID if some tool dropped the name.
+pub fn load_xliff(path: &Path, overrides: &mut HashMap<String, String>) -> Result<(), Error> {
+    let mut reader = Reader::from_file(path)?;
+    let mut buf = Vec::new();
+
+    let mut elements = Vec::<Vec<u8>>::new();
+    let mut unit_id = None;
+    let mut target_text: Option<String> = None;
+    // The depth of the current translation element, to collect the text of inline elements too
+    let mut target_depth = None;
+
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Start(element) => {
+                let name = element.local_name().as_ref().to_vec();
+                let parent = elements.last().map(Vec::as_slice);
+                match name.as_slice() {
+                    b"trans-unit" => {
+                        unit_id = match element.try_get_attribute("id")? {
+                            Some(id) => Some(id.unescape_value()?.into_owned()),
+                            None => None,
+                        };
+                        target_text = None;
+                    },
+                    b"unit" => {
+                        let name = element.try_get_attribute("name")?;
+                        unit_id = match (name, element.try_get_attribute("id")?) {
+                            (Some(name), _) => Some(name.unescape_value()?.into_owned()),
+                            (None, Some(id)) => {
+                                let id = id.unescape_value()?;
+                                Some(from_xliff_id(&id).unwrap_or_else(|| id.into_owned()))
+                            },
+                            (None, None) => None,
+                        };
+                        target_text = None;
+                    },
+                    b"target" if matches!(parent, Some(b"trans-unit" | b"segment")) => {
+                        target_depth = Some(elements.len());
+                        target_text.get_or_insert_with(String::new);
+                    },
+                    _ => {},
+                }
+                elements.push(name);
+            },
+            Event::End(_) => {
+                let name = elements.pop().unwrap_or_default();
+                if target_depth == Some(elements.len()) {
+                    target_depth = None;
+                }
+                if matches!(name.as_slice(), b"trans-unit" | b"unit")
+                    && let (Some(key), Some(text)) = (unit_id.take(), target_text.take())
+                    && !text.is_empty()
+                {
+                    overrides.insert(key, text);
+                }
+            },
+            Event::Text(text) if target_depth.is_some() => {
+                target_text
+                    .get_or_insert_with(String::new)
+                    .push_str(&text.unescape()?);
+            },
+            Event::CData(text) if target_depth.is_some() => {
+                target_text
+                    .get_or_insert_with(String::new)
+                    .push_str(std::str::from_utf8(&text)?);
+            },
+            Event::Eof => break,
+            _ => {},
+        }
+        buf.clear();
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, path::PathBuf, process};
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("nxdtext-xliff-{}-{}", process::id(), name))
+    }
+
+    fn load_str(name: &str, content: &str) -> HashMap<String, String> {
+        let path = temp_path(name);
+        fs::write(&path, content).unwrap();
+        let mut overrides = HashMap::new();
+        load_xliff(&path, &mut overrides).unwrap();
+        fs::remove_file(path).unwrap();
+        overrides
+    }
+
+    #[test]
+    fn id_round_trip() {
+        let key = "ability_x/1042/15[0] ÿé";
+        let id = to_xliff_id(key);
+        assert!(id.bytes().all(|byte| byte.is_ascii_alphanumeric() || b"_.-".contains(&byte)));
+        assert!(id.starts_with("ability_5Fx_2F1042_2F15_5B0_5D_20"));
+        assert_eq!(from_xliff_id(&id).as_deref(), Some(key));
+
+        assert_eq!(from_xliff_id("table_2"), None);
+        assert_eq!(from_xliff_id("table_ZZ"), None);
+    }
+
+    #[test]
+    fn load_1_2() {
+        let overrides = load_str(
+            "1.2.xlf",
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <xliff version="1.2" xmlns="urn:oasis:names:tc:xliff:document:1.2">
+              <file original="charaname" source-language="en" datatype="plaintext">
+                <body>
+                  <trans-unit id="charaname/10/2">
+                    <source>Ramza</source>
+                    <target>Ram<g id="1">za</g> &amp; co</target>
+                    <alt-trans><target>Alternative</target></alt-trans>
+                  </trans-unit>
+                  <trans-unit id="charaname/11/2">
+                    <source>Delita</source>
+                    <alt-trans><target>Alternative</target></alt-trans>
+                  </trans-unit>
+                  <trans-unit id="charaname/15/2">
+                    <source>Ramza</source>
+                    <target/>
+                  </trans-unit>
+                </body>
+              </file>
+            </xliff>"#,
+        );
+        let expected = HashMap::from([("charaname/10/2".to_owned(), "Ramza & co".to_owned())]);
+        assert_eq!(overrides, expected);
+    }
+
+    #[test]
+    fn load_2_0() {
+        let overrides = load_str(
+            "2.0.xlf",
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <xliff version="2.0" xmlns="urn:oasis:names:tc:xliff:document:2.0"
+                srcLang="en" trgLang="fr">
+              <file id="uisubtitles">
+                <unit id="uisubtitles_2F30_2F1_2F2" name="uisubtitles/30/1/2">
+                  <segment>
+                    <source>Hello</source>
+                    <target>Sal<pc id="1">ut</pc></target>
+                  </segment>
+                </unit>
+                <unit id="uisubtitles_2F31_2F1_2F2">
+                  <segment>
+                    <source>Third</source>
+                    <target><![CDATA[Trois<i>]]></target>
+                  </segment>
+                </unit>
+              </file>
+            </xliff>"#,
+        );
+        let expected = HashMap::from([
+            ("uisubtitles/30/1/2".to_owned(), "Salut".to_owned()),
+            ("uisubtitles/31/1/2".to_owned(), "Trois<i>".to_owned()),
+        ]);
+        assert_eq!(overrides, expected);
+    }
+
+    #[test]
+    fn save_and_load() {
+        let rows = [
+            ("charaname/10/2".to_owned(), "Ramza".to_owned()),
+            ("charaname/11/6[1]".to_owned(), "Delita \"alias\"".to_owned()),
+        ];
+        let target_texts = rows
+            .iter()
+            .map(|(key, text)| (key.clone(), format!("{} é", text)))
+            .collect::<HashMap<_, _>>();
+
+        for xliff_version in [CliXliffVersion::V1_2, CliXliffVersion::V2_0] {
+            let options = CliFormatOptions {
+                xliff_version,
+                source_lang: "en".to_owned(),
+                target_lang: Some("fr".to_owned()),
+                csv_context: false,
+            };
+            let path = temp_path("round_trip.xlf");
+            let schemas = nxd::Schemas::default();
+            save_xliff("charaname", &rows, Some(&target_texts), &schemas, &options, &path).unwrap();
+
+            let mut overrides = HashMap::new();
+            load_xliff(&path, &mut overrides).unwrap();
+            fs::remove_file(path).unwrap();
+            assert_eq!(overrides, target_texts);
+        }
+    }
+}
@@ -11,10 +11,16 @@ pub fn read_u32(reader: &mut impl ReadBytesExt) -> io::Result<u32> {
 pub fn read_i32(reader: &mut impl ReadBytesExt) -> io::Result<i32> {
     reader.read_i32::<LittleEndian>()
 }
+pub fn read_u64(reader: &mut impl ReadBytesExt) -> io::Result<u64> {
+    reader.read_u64::<LittleEndian>()
+}
 
 pub fn write_u32(value: u32, writer: &mut impl WriteBytesExt) -> io::Result<()> {
     writer.write_u32::<LittleEndian>(value)
 }
+pub fn write_u64(value: u64, writer: &mut impl WriteBytesExt) -> io::Result<()> {
+    writer.write_u64::<LittleEndian>(value)
+}
 
 
 pub fn read_cstr(reader: &mut (impl ReadBytesExt + Seek)) -> Result<String, NxdError> {
@@ -0,0 +1,155 @@
+// Copyright (C) 2025  Mátyás Mustoha
+
+use crate::{
+    binary::read_u32,
+    error::NxdError,
+    nxd::read_nxd_header,
+    nxd_tables::Cell,
+    schema::TableSchema,
+};
+use byteorder::ReadBytesExt;
+use std::io::Seek;
+
+
+/// Checks whether every value of a column, read as a pointer relative to `shift` cells
+/// from itself, points to the start of a valid text. Returns whether all of them are empty.
+fn check_str_column(data: &[u8], textarea_pos: u64, cells: &[(u64, u32)], shift: i64) -> Option<bool> {
+    let mut all_empty = true;
+
+    for &(cell_pos, raw) in cells {
+        let target = (cell_pos as i64) + shift * 4 + (raw as i32 as i64);
+        let target = u64::try_from(target).ok().filter(|target| *target >= textarea_pos)? as usize;
+        if target >= data.len() || (target as u64 > textarea_pos && data[target - 1] != 0) {
+            return None;
+        }
+
+        let len = data[target..].iter().position(|byte| *byte == 0)?;
+        std::str::from_utf8(&data[target..target + len]).ok()?;
+        all_empty &= len == 0;
+    }
+    Some(all_empty)
+}
+
+
+fn is_plausible_float(raw: u32) -> bool {
+    let value = f32::from_bits(raw);
+    raw == 0 || (value.is_normal() && (1e-4..=1e6).contains(&value.abs()))
+}
+
+
+fn guess_cell(data: &[u8], textarea_pos: u64, cells: &[(u64, u32)], col: usize) -> Cell {
+    if cells.iter().all(|(_, raw)| *raw == 0) {
+        return Cell::Zero32;
+    }
+
+    // A text pointer is relative either to itself, or to one of the earlier cells of the row
+    let max_back = col.min(i8::MAX as usize) as i8;
+    for shift in (-max_back..=0).rev() {
+        match check_str_column(data, textarea_pos, cells, shift as i64) {
+            Some(true) if shift == 0 => return Cell::EmptyStr,
+            Some(_) => return Cell::Str(shift),
+            None => {},
+        }
+    }
+
+    if cells.iter().all(|(_, raw)| *raw <= 1) {
+        Cell::Bool32
+    } else if cells.iter().all(|(_, raw)| is_plausible_float(*raw)) {
+        Cell::F32
+    } else if cells.iter().any(|(_, raw)| (*raw as i32).is_negative()) {
+        Cell::I32
+    } else {
+        Cell::U32
+    }
+}
+
+
+/// Guesses the column layout of a table of unknown type from the values of every row.
+/// The row size is derived from the distance of the rows, so at least two are needed.
+/// Arrays are not recognized, their offset and count show up as separate columns.
+pub fn discover_schema(reader: &mut (impl ReadBytesExt + Seek)) -> Result<TableSchema, NxdError> {
+    let (_, _, rowinfos) = read_nxd_header(reader)?;
+    let rowinfos_end = reader.stream_position()?;
+
+    let mut data = Vec::new();
+    reader.rewind()?;
+    reader.read_to_end(&mut data)?;
+
+    let mut row_positions = rowinfos
+        .iter()
+        .map(|rowinfo| rowinfo.rowdata_pos.abs_target_from(rowinfo.self_pos))
+        .collect::<Result<Vec<_>, _>>()?;
+    row_positions.sort_unstable();
+    row_positions.dedup();
+
+    let row_size = row_positions
+        .windows(2)
+        .map(|pair| pair[1] - pair[0])
+        .min()
+        .ok_or(NxdError::TooFewRows)?;
+    if row_size % 4 != 0 {
+        return Err(NxdError::InvalidHeader);
+    }
+
+    let last_row_end = row_positions[row_positions.len() - 1] + row_size;
+    let textarea_pos = std::cmp::max(rowinfos_end, last_row_end);
+    if textarea_pos > data.len() as u64 {
+        return Err(NxdError::InvalidHeader);
+    }
+
+    let columns = (0..(row_size / 4) as usize)
+        .map(|col| {
+            let cells = row_positions
+                .iter()
+                .map(|row_pos| {
+                    let cell_pos = row_pos + (col as u64) * 4;
+                    Ok((cell_pos, read_u32(&mut &data[cell_pos as usize..])?))
+                })
+                .collect::<Result<Vec<_>, NxdError>>()?;
+            Ok(guess_cell(&data, textarea_pos, &cells, col))
+        })
+        .collect::<Result<Vec<_>, NxdError>>()?;
+
+    Ok(TableSchema::from_cells(&columns))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nxd_writer::{
+        tests::{sample_schemas, sample_table, TABLENAME},
+        write_table,
+    };
+    use std::io::Cursor;
+
+    /// The scalar columns of the sample table, as arrays are not discovered.
+    const SCALAR_COLUMNS: usize = 5;
+
+    #[test]
+    fn discover_sample_table() {
+        let expected = &sample_schemas().get(TABLENAME).unwrap().cells()[..SCALAR_COLUMNS];
+        assert!(expected.contains(&Cell::Str(-1)));
+
+        for is_double_key in [false, true] {
+            let mut table = sample_table(is_double_key);
+            table.columns.truncate(SCALAR_COLUMNS);
+            for row in &mut table.rows {
+                row.cells.truncate(SCALAR_COLUMNS);
+            }
+            let data = write_table(&table).unwrap();
+
+            let schema = discover_schema(&mut Cursor::new(&data)).unwrap();
+            assert_eq!(schema.cells(), expected);
+            assert!(schema.columns.iter().all(|column| column.name.is_none()));
+        }
+    }
+
+    #[test]
+    fn too_few_rows() {
+        let mut table = sample_table(false);
+        table.rows.truncate(1);
+        let data = write_table(&table).unwrap();
+        assert!(matches!(discover_schema(&mut Cursor::new(&data)), Err(NxdError::TooFewRows)));
+    }
+}
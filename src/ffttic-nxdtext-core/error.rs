@@ -3,13 +3,47 @@
 use std::{fmt, io};
 
 
+#[derive(Debug)]
 pub enum NxdError {
     Io(io::Error),
     InvalidHeader,
+    PacArchive,
+    PacEncrypted,
+    CompressedPacEntry {
+        path: String,
+    },
+    UnknownPacEntry {
+        path: String,
+    },
     UnsupportedFormat,
     Utf8Error {
         offset: u64,
     },
+    InvalidRowKey {
+        row: usize,
+    },
+    InvalidCell {
+        row: usize,
+        col: usize,
+    },
+    InvalidValue {
+        key: String,
+    },
+    InvalidSetInfo {
+        set: usize,
+    },
+    NoSetInfo,
+    DuplicateRowKey {
+        key1: u32,
+        key2: Option<u32>,
+    },
+    InvalidSchema(String),
+    TooFewRows,
+    UnmappedData {
+        offset: u64,
+    },
+    LegacyKeys,
+    AmbiguousKeys,
 
     RowContext {
         row: usize,
@@ -33,10 +67,55 @@ impl fmt::Display for NxdError {
         match self {
             NxdError::Io(ioerr) => write!(f, "I/O error: {}", ioerr),
             NxdError::InvalidHeader => write!(f, "Invalid file header"),
+            NxdError::PacArchive => write!(f, "The file is a .pac archive, not an NXD table"),
+            NxdError::PacEncrypted => write!(f, "The file names of the archive are encrypted, which is not supported"),
+            NxdError::CompressedPacEntry { path } => write!(
+                f,
+                "`{}` is compressed with Oodle, which is not supported. Extract it with eg. FF16Tools instead",
+                path
+            ),
+            NxdError::UnknownPacEntry { path } => write!(f, "The archive has no file named `{}`", path),
             NxdError::UnsupportedFormat => write!(f, "Unsupported format"),
             NxdError::Utf8Error { offset } => {
                 write!(f, "The text that starts at offset {} is not a valid UTF-8 sequence", offset)
             },
+            NxdError::InvalidRowKey { row } => {
+                write!(f, "The keys of row {} do not match the type of the table", row)
+            },
+            NxdError::InvalidCell { row, col } => {
+                write!(f, "Cell {} of row {} does not match the column definitions", col, row)
+            },
+            NxdError::InvalidValue { key } => {
+                write!(f, "The value of `{}` does not fit the type of its column", key)
+            },
+            NxdError::InvalidSetInfo { set } => {
+                write!(f, "Set info {} does not match the rows of the table", set)
+            },
+            NxdError::NoSetInfo => write!(f, "Only tables with two keys have set info"),
+            NxdError::DuplicateRowKey { key1, key2: Some(key2) } => {
+                write!(f, "A row with the keys {}/{} already exists", key1, key2)
+            },
+            NxdError::DuplicateRowKey { key1, key2: None } => {
+                write!(f, "A row with the key {} already exists", key1)
+            },
+            NxdError::InvalidSchema(message) => write!(f, "Invalid schema file {}", message),
+            NxdError::TooFewRows => {
+                write!(f, "The layout can only be discovered in tables with at least two rows")
+            },
+            NxdError::UnmappedData { offset } => {
+                write!(f, "The data at offset {} is not part of the known layout and would be lost", offset)
+            },
+            NxdError::LegacyKeys => write!(
+                f,
+                "The translation keys are based on the position of the rows, like in earlier versions. \
+                Convert them with the `migrate` command first"
+            ),
+            NxdError::AmbiguousKeys => write!(
+                f,
+                "The translation keys could be based either on the keys or the position of the rows. \
+                Convert them with the `migrate` command if they come from an earlier version, \
+                or import them with `--current-keys`"
+            ),
             NxdError::RowContext { row, source } => {
                 write!(f, "Error when trying to read row {}:\n  {}", row, source)
             },
@@ -16,9 +16,23 @@
 #![forbid(unsafe_code)]
 
 mod binary;
+mod discover;
 mod error;
 mod nxd;
+mod nxd_table;
 mod nxd_tables;
+mod nxd_writer;
+mod pac;
+mod schema;
 
+pub use discover::discover_schema;
 pub use error::NxdError;
-pub use nxd::{read_rows, update_rows};
+pub use nxd::{
+    check_legacy_keys, check_regions, read_key_migration, read_rows, read_rows_by_set, read_set_infos, read_table,
+    read_values, update_rows, update_table, update_values, ImportReport, TextLayout,
+};
+pub use nxd_table::{CellValue, NxdHeader, NxdLocalizationType, NxdRow, NxdRowType, NxdSetInfo, NxdTable, Value};
+pub use nxd_tables::Cell;
+pub use nxd_writer::write_table;
+pub use pac::{extract_pac_entry, read_pac_entries, repack_pac, PacEntry};
+pub use schema::{Column, Schemas, TableSchema};
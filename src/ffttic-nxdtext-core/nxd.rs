@@ -3,17 +3,24 @@
 use crate::{
     binary::*,
     error::NxdError,
-    nxd_tables::{Cell, NXD_COLUMNS},
+    nxd_table::*,
+    nxd_tables::Cell,
+    pac::PAC_MAGIC,
+    schema::Schemas,
 };
 use byteorder::ReadBytesExt;
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, HashSet},
     io::{Cursor, Seek, SeekFrom, Write},
+    ops::Range,
 };
 
 
-const NXD_MAGIC: u32 = u32::from_le_bytes(*b"NXDF");
-const NXD_FORMAT: u32 = 1;
+pub(crate) const NXD_MAGIC: u32 = u32::from_le_bytes(*b"NXDF");
+pub(crate) const NXD_FORMAT: u32 = 1;
+pub(crate) const ROWINFO_1KEY_SIZE: u64 = 8;
+pub(crate) const ROWINFO_2KEY_SIZE: u64 = 12;
+pub(crate) const SETINFO_SIZE: u64 = 12;
 
 
 fn safe_pos_add(base: u64, delta: i32) -> Result<u64, NxdError> {
@@ -27,7 +34,7 @@ fn safe_pos_add(base: u64, delta: i32) -> Result<u64, NxdError> {
 
 
 #[derive(Clone, Debug)]
-struct Pointer {
+pub(crate) struct Pointer {
     self_pos: u64,
     rel_offset: i32,
 }
@@ -47,19 +54,19 @@ impl Pointer {
 
 
 #[derive(Clone, Debug)]
-struct RowInfo {
-    self_pos: u64,
-    _row_key1: u32,
-    _row_key2: Option<u32>,
-    rowdata_pos: Pointer,
+pub(crate) struct RowInfo {
+    pub(crate) self_pos: u64,
+    row_key1: u32,
+    row_key2: Option<u32>,
+    pub(crate) rowdata_pos: Pointer,
 }
 
 impl RowInfo {
     pub fn read_1key(reader: &mut (impl ReadBytesExt + Seek)) -> Result<Self, NxdError> {
         Ok(Self {
             self_pos: reader.stream_position()?,
-            _row_key1: read_u32(reader)?,
-            _row_key2: None,
+            row_key1: read_u32(reader)?,
+            row_key2: None,
             rowdata_pos: Pointer::read(reader)?,
         })
     }
@@ -67,8 +74,8 @@ impl RowInfo {
     pub fn read_2key(reader: &mut (impl ReadBytesExt + Seek)) -> Result<Self, NxdError> {
         Ok(Self {
             self_pos: reader.stream_position()?,
-            _row_key1: read_u32(reader)?,
-            _row_key2: Some(read_u32(reader)?),
+            row_key1: read_u32(reader)?,
+            row_key2: Some(read_u32(reader)?),
             rowdata_pos: Pointer::read(reader)?,
         })
     }
@@ -87,40 +94,62 @@ fn read_key1_rowinfos(reader: &mut (impl ReadBytesExt + Seek)) -> Result<Vec<Row
     Ok(rowinfos)
 }
 
-fn read_key2_rowinfos(reader: &mut (impl ReadBytesExt + Seek)) -> Result<Vec<RowInfo>, NxdError> {
-    let _setinfo_pos = Pointer::read(reader)?;
-    let _setinfo_count = read_u32(reader)?;
+
+fn read_setinfo(
+    reader: &mut (impl ReadBytesExt + Seek),
+    rowinfo_pos_abs: u64,
+) -> Result<NxdSetInfo, NxdError> {
+    let self_pos = reader.stream_position()?;
+    let key1 = read_u32(reader)?;
+    let rows_pos = Pointer::read(reader)?;
+    let row_count = read_u32(reader)? as usize;
+
+    let first_row = rows_pos
+        .abs_target_from(self_pos)?
+        .checked_sub(rowinfo_pos_abs)
+        .filter(|distance| distance % ROWINFO_2KEY_SIZE == 0)
+        .map(|distance| (distance / ROWINFO_2KEY_SIZE) as usize)
+        .ok_or(NxdError::InvalidHeader)?;
+
+    Ok(NxdSetInfo {
+        key1,
+        first_row,
+        row_count,
+    })
+}
+
+fn read_key2_rowinfos(
+    reader: &mut (impl ReadBytesExt + Seek),
+) -> Result<(Vec<NxdSetInfo>, Vec<RowInfo>), NxdError> {
+    let setinfo_pos = Pointer::read(reader)?;
+    let setinfo_count = read_u32(reader)?;
     let _blank = read_u32(reader)?;
     let rowinfo_pos_abs = read_u32(reader)? as u64;
     let rowinfo_count = read_u32(reader)?;
 
+    reader.seek(SeekFrom::Start(setinfo_pos.abs_target_from(setinfo_pos.self_pos)?))?;
+
+    let setinfos = (0..setinfo_count)
+        .map(|_| read_setinfo(reader, rowinfo_pos_abs))
+        .collect::<Result<Vec<_>, _>>()?;
+
     reader.seek(SeekFrom::Start(rowinfo_pos_abs))?;
 
     let rowinfos = (0..rowinfo_count)
         .map(|_| RowInfo::read_2key(reader))
         .collect::<Result<Vec<_>, _>>()?;
-    Ok(rowinfos)
-}
-
-
-enum NxdRowType {
-    SingleKey = 1,
-    DoubleKey,
-}
-
-#[derive(PartialEq)]
-enum NxdLocalizationType {
-    SingleKeyUnlocalized = 1,
-    SingleKeyLocalized,
-    DoubleKeyUnlocalized,
-    DoubleKeyLocalized,
+    Ok((setinfos, rowinfos))
 }
 
 
-fn read_nxd_header(reader: &mut (impl ReadBytesExt + Seek)) -> Result<Vec<RowInfo>, NxdError> {
-    let magic = read_u32(reader)?;
-    if magic != NXD_MAGIC {
-        return Err(NxdError::InvalidHeader);
+/// Reads the header and the row infos. When this returns, the reader is at the end of the row info array.
+pub(crate) fn read_nxd_header(
+    reader: &mut (impl ReadBytesExt + Seek),
+) -> Result<(NxdHeader, Vec<NxdSetInfo>, Vec<RowInfo>), NxdError> {
+    match read_u32(reader)? {
+        NXD_MAGIC => {},
+        PAC_MAGIC => return Err(NxdError::PacArchive),
+        _ => return Err(NxdError::InvalidHeader),
     }
 
     let format = read_u32(reader)?;
@@ -130,52 +159,144 @@ fn read_nxd_header(reader: &mut (impl ReadBytesExt + Seek)) -> Result<Vec<RowInf
 
     let table_rowtype = reader.read_u8()?;
     let table_localization = reader.read_u8()?;
-    let _uses_base_rowid = reader.read_u8()?;
+    let uses_base_rowid = reader.read_u8()? != 0;
     let _blank = reader.read_u8()?;
-    let _base_rowid = read_u32(reader);
-    reader.seek_relative(4 * 4)?;
+    let base_rowid = read_u32(reader)?;
+    let mut unknown = [0; 4];
+    for field in unknown.iter_mut() {
+        *field = read_u32(reader)?;
+    }
 
     match table_rowtype {
         f if f == NxdRowType::SingleKey as u8 => {
-            let valid_localizations = &[
-                NxdLocalizationType::SingleKeyUnlocalized as u8,
-                NxdLocalizationType::SingleKeyLocalized as u8,
-            ];
-            if !valid_localizations.contains(&table_localization) {
-                return Err(NxdError::InvalidHeader);
-            }
-            read_key1_rowinfos(reader)
+            let localization = match table_localization {
+                l if l == NxdLocalizationType::SingleKeyUnlocalized as u8 => NxdLocalizationType::SingleKeyUnlocalized,
+                l if l == NxdLocalizationType::SingleKeyLocalized as u8 => NxdLocalizationType::SingleKeyLocalized,
+                _ => return Err(NxdError::InvalidHeader),
+            };
+            let header = NxdHeader {
+                row_type: NxdRowType::SingleKey,
+                localization,
+                uses_base_rowid,
+                base_rowid,
+                unknown,
+            };
+            let rowinfos = read_key1_rowinfos(reader)?;
+            Ok((header, Vec::new(), rowinfos))
         },
         f if f == NxdRowType::DoubleKey as u8 => {
-            let valid_localizations = &[
-                NxdLocalizationType::DoubleKeyUnlocalized as u8,
-                NxdLocalizationType::DoubleKeyLocalized as u8,
-            ];
-            if !valid_localizations.contains(&table_localization) {
-                return Err(NxdError::InvalidHeader);
-            }
-            read_key2_rowinfos(reader)
+            let localization = match table_localization {
+                l if l == NxdLocalizationType::DoubleKeyUnlocalized as u8 => NxdLocalizationType::DoubleKeyUnlocalized,
+                l if l == NxdLocalizationType::DoubleKeyLocalized as u8 => NxdLocalizationType::DoubleKeyLocalized,
+                _ => return Err(NxdError::InvalidHeader),
+            };
+            let header = NxdHeader {
+                row_type: NxdRowType::DoubleKey,
+                localization,
+                uses_base_rowid,
+                base_rowid,
+                unknown,
+            };
+            let (setinfos, rowinfos) = read_key2_rowinfos(reader)?;
+            Ok((header, setinfos, rowinfos))
         },
         _ => Err(NxdError::UnsupportedFormat),
     }
 }
 
 
+/// The byte ranges taken by the header, the set infos and the row infos.
+fn read_header_regions(
+    reader: &mut (impl ReadBytesExt + Seek),
+) -> Result<Vec<Range<u64>>, NxdError> {
+    reader.seek(SeekFrom::Start(0x08))?;
+    let table_rowtype = reader.read_u8()?;
+    reader.seek(SeekFrom::Start(0x20))?;
+
+    if table_rowtype == NxdRowType::DoubleKey as u8 {
+        let setinfo_pos = Pointer::read(reader)?;
+        let setinfo_count = read_u32(reader)? as u64;
+        let _blank = read_u32(reader)?;
+        let rowinfo_pos_abs = read_u32(reader)? as u64;
+        let rowinfo_count = read_u32(reader)? as u64;
+
+        let setinfo_pos_abs = setinfo_pos.abs_target_from(setinfo_pos.self_pos)?;
+        Ok(vec![
+            0..reader.stream_position()?,
+            setinfo_pos_abs..setinfo_pos_abs + setinfo_count * SETINFO_SIZE,
+            rowinfo_pos_abs..rowinfo_pos_abs + rowinfo_count * ROWINFO_2KEY_SIZE,
+        ])
+    } else {
+        let rowinfo_pos_abs = read_u32(reader)? as u64;
+        let rowinfo_count = read_u32(reader)? as u64;
+        Ok(vec![
+            0..reader.stream_position()?,
+            rowinfo_pos_abs..rowinfo_pos_abs + rowinfo_count * ROWINFO_1KEY_SIZE,
+        ])
+    }
+}
+
+
+/// Reads the offset and the count of an array cell. Returns the absolute position
+/// of the first element.
+fn read_array_info(
+    reader: &mut (impl ReadBytesExt + Seek),
+    relative_field: i8,
+) -> Result<(u64, u32), NxdError> {
+    let ptr = Pointer::read(reader)?;
+    let count = read_u32(reader)?;
+    let ptr_base = safe_pos_add(ptr.self_pos, (relative_field as i32) * 4)?;
+    Ok((ptr.abs_target_from(ptr_base)?, count))
+}
+
+
 fn read_cell(
     reader: &mut (impl ReadBytesExt + Seek),
     cell_type: &Cell,
-) -> Result<Option<String>, NxdError> {
+) -> Result<CellValue, NxdError> {
     match cell_type {
-        Cell::Zero32 | Cell::Bool32 | Cell::Skip32 | Cell::EmptyStr => {
+        Cell::Zero32 => Ok(CellValue::Zero32(read_u32(reader)?)),
+        Cell::Bool32 => Ok(CellValue::Bool32(read_u32(reader)?)),
+        Cell::Skip32 => Ok(CellValue::Skip32(read_u32(reader)?)),
+        Cell::U32 => Ok(CellValue::U32(read_u32(reader)?)),
+        Cell::I32 => Ok(CellValue::I32(read_i32(reader)?)),
+        Cell::F32 => Ok(CellValue::F32(f32::from_bits(read_u32(reader)?))),
+        Cell::EmptyStr => {
             read_u32(reader)?;
-            Ok(None)
+            Ok(CellValue::EmptyStr)
         },
         Cell::Str(relative_field) => {
             let ptr = Pointer::read(reader)?;
             let ptr_base = safe_pos_add(ptr.self_pos, (*relative_field as i32) * 4)?;
             let text_base = ptr.abs_target_from(ptr_base)?;
             let text = read_cstr_at(reader, text_base)?;
-            Ok(Some(text))
+            Ok(CellValue::Str(text))
+        },
+        Cell::U32Array(relative_field) | Cell::StrArray(relative_field) => {
+            let (array_pos, count) = read_array_info(reader, *relative_field)?;
+            let cell_end = reader.stream_position()?;
+            reader.seek(SeekFrom::Start(array_pos))?;
+
+            let value = match cell_type {
+                Cell::U32Array(_) => {
+                    let values = (0..count)
+                        .map(|_| read_u32(reader))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    CellValue::U32Array(values)
+                },
+                _ => {
+                    let texts = (0..count)
+                        .map(|_| {
+                            let ptr = Pointer::read(reader)?;
+                            read_cstr_at(reader, ptr.abs_target_from(ptr.self_pos)?)
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    CellValue::StrArray(texts)
+                },
+            };
+
+            reader.seek(SeekFrom::Start(cell_end))?;
+            Ok(value)
         },
     }
 }
@@ -185,160 +306,1014 @@ fn read_row(
     reader: &mut (impl ReadBytesExt + Seek),
     row_definition: &[Cell],
     rowinfo: &RowInfo,
-) -> Result<Vec<(usize, String)>, NxdError> {
+) -> Result<Vec<CellValue>, NxdError> {
     let rowdata_pos = rowinfo.rowdata_pos.abs_target_from(rowinfo.self_pos)?;
     reader.seek(SeekFrom::Start(rowdata_pos))?;
 
-    let cells = row_definition
+    row_definition
         .iter()
         .enumerate()
         .map(|(cell_idx, cell_type)| {
             let cell_pos = reader.stream_position()?;
-            read_cell(reader, &cell_type)
+            read_cell(reader, cell_type)
                 .map_err(|err| NxdError::CellContext {
                     col: cell_idx,
                     offset: cell_pos,
                     source: Box::new(err),
                 })
         })
-        .collect::<Result<Vec<_>, _>>()?
+        .collect::<Result<Vec<_>, _>>()
+}
+
+
+/// The text cells of a row, along with their column index.
+type TextRow = Vec<(usize, String)>;
+
+fn into_text_row(cells: Vec<CellValue>) -> TextRow {
+    cells
         .into_iter()
         .enumerate()
-        .filter_map(|(idx, opt)| opt.map(|text| (idx, text)))
-        .collect::<Vec<_>>();
-    Ok(cells)
+        .filter_map(|(idx, cell)| match cell {
+            CellValue::Str(text) => Some((idx, text)),
+            _ => None,
+        })
+        .collect()
+}
+
+
+fn read_all_rows(
+    reader: &mut (impl ReadBytesExt + Seek),
+    row_definition: &[Cell],
+    rowinfos: &[RowInfo],
+) -> Result<Vec<Vec<CellValue>>, NxdError> {
+    rowinfos
+        .iter()
+        .enumerate()
+        .map(|(row_idx, rowinfo)| {
+            read_row(reader, row_definition, rowinfo)
+                .map_err(|err| NxdError::RowContext {
+                    row: row_idx,
+                    source: Box::new(err),
+                })
+        })
+        .collect()
 }
 
 
-fn create_translation_key(tablename: &str, row_idx: usize, cell_idx: usize) -> String {
+fn create_translation_key(tablename: &str, row_key1: u32, row_key2: Option<u32>, cell_idx: usize) -> String {
+    match row_key2 {
+        Some(row_key2) => format!("{}/{}/{}/{}", tablename, row_key1, row_key2, cell_idx),
+        None => format!("{}/{}/{}", tablename, row_key1, cell_idx),
+    }
+}
+
+/// The key of an element of an array cell, eg. `ability/1042/5[0]`.
+fn create_element_key(
+    tablename: &str,
+    row_key1: u32,
+    row_key2: Option<u32>,
+    cell_idx: usize,
+    elem_idx: usize,
+) -> String {
+    format!("{}[{}]", create_translation_key(tablename, row_key1, row_key2, cell_idx), elem_idx)
+}
+
+/// The positions of the cells relative to the start of the row.
+fn cell_offsets(row_definition: &[Cell]) -> Vec<u64> {
+    row_definition
+        .iter()
+        .scan(0, |offset, cell| {
+            let cell_offset = *offset;
+            *offset += cell.size();
+            Some(cell_offset)
+        })
+        .collect()
+}
+
+/// Splits a translation key into the row key(s) and the column index.
+fn parse_translation_key(tablename: &str, key: &str) -> Option<(u32, Option<u32>, usize)> {
+    let mut parts = key.split('/');
+    if parts.next()? != tablename {
+        return None;
+    }
+    let numbers = parts
+        .map(|part| part.parse::<u32>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    match numbers[..] {
+        [row_key1, cell_idx] => Some((row_key1, None, cell_idx as usize)),
+        [row_key1, row_key2, cell_idx] => Some((row_key1, Some(row_key2), cell_idx as usize)),
+        _ => None,
+    }
+}
+
+/// The key format of earlier versions, based on the position of the row in the file.
+fn create_legacy_translation_key(tablename: &str, row_idx: usize, cell_idx: usize) -> String {
     format!("{}/{}/{}", tablename, row_idx, cell_idx)
 }
 
 
+fn read_text_rows(
+    reader: &mut (impl ReadBytesExt + Seek),
+    row_definition: &[Cell],
+) -> Result<(Vec<RowInfo>, Vec<TextRow>), NxdError> {
+    let (_, _, rowinfos) = read_nxd_header(reader)?;
+    let rows = read_all_rows(reader, row_definition, &rowinfos)?
+        .into_iter()
+        .map(into_text_row)
+        .collect();
+    Ok((rowinfos, rows))
+}
+
+
+pub fn read_table(
+    reader: &mut (impl ReadBytesExt + Seek),
+    tablename: &str,
+    schemas: &Schemas,
+) -> Result<NxdTable, NxdError> {
+    let row_definition = schemas
+        .get(tablename)
+        .ok_or(NxdError::UnsupportedFormat)?
+        .cells();
+
+    let (header, set_infos, rowinfos) = read_nxd_header(reader)?;
+    let rows = read_all_rows(reader, &row_definition, &rowinfos)?
+        .into_iter()
+        .zip(rowinfos)
+        .map(|(cells, rowinfo)| NxdRow {
+            key1: rowinfo.row_key1,
+            key2: rowinfo.row_key2,
+            cells,
+        })
+        .collect();
+
+    let table = NxdTable {
+        header,
+        columns: row_definition,
+        set_infos,
+        rows,
+    };
+    table.check_set_infos()?;
+    Ok(table)
+}
+
+
+/// Reads the set infos of a double-key table. Works on tables without a known layout too.
+pub fn read_set_infos(reader: &mut (impl ReadBytesExt + Seek)) -> Result<Vec<NxdSetInfo>, NxdError> {
+    let (_, set_infos, _) = read_nxd_header(reader)?;
+    Ok(set_infos)
+}
+
+
+/// The values of a row along with their keys. Every element of an array gets a key of its own.
+fn row_values<'a>(
+    tablename: &'a str,
+    row_key1: u32,
+    row_key2: Option<u32>,
+    cells: &'a [CellValue],
+) -> impl Iterator<Item = (String, Value)> + 'a {
+    cells.iter().enumerate().flat_map(move |(cell_idx, cell)| {
+        let value = cell
+            .to_value()
+            .map(|value| (create_translation_key(tablename, row_key1, row_key2, cell_idx), value));
+        let elements = cell
+            .to_element_values()
+            .into_iter()
+            .enumerate()
+            .map(move |(elem_idx, value)| {
+                let key = create_element_key(tablename, row_key1, row_key2, cell_idx, elem_idx);
+                (key, value)
+            });
+        value.into_iter().chain(elements)
+    })
+}
+
+fn row_text_entries<'a>(
+    tablename: &'a str,
+    row_key1: u32,
+    row_key2: Option<u32>,
+    cells: &'a [CellValue],
+) -> impl Iterator<Item = (String, String)> + 'a {
+    row_values(tablename, row_key1, row_key2, cells).filter_map(|(key, value)| match value {
+        Value::Text(text) => Some((key, text)),
+        _ => None,
+    })
+}
+
+
+/// Translation keys and their texts.
+pub type TextEntries = Vec<(String, String)>;
+
+/// Like `read_rows`, but with the entries grouped by the sets of a double-key table.
+pub fn read_rows_by_set(
+    reader: &mut (impl ReadBytesExt + Seek),
+    tablename: &str,
+    schemas: &Schemas,
+) -> Result<Vec<(u32, TextEntries)>, NxdError> {
+    let table = read_table(reader, tablename, schemas)?;
+    if !table.is_double_key() {
+        return Err(NxdError::NoSetInfo);
+    }
+
+    let sets = table
+        .set_infos
+        .iter()
+        .map(|setinfo| {
+            let entries = table.rows[setinfo.rows()]
+                .iter()
+                .flat_map(|row| row_text_entries(tablename, row.key1, row.key2, &row.cells))
+                .collect();
+            (setinfo.key1, entries)
+        })
+        .collect();
+    Ok(sets)
+}
+
+
 pub fn read_rows(
     reader: &mut (impl ReadBytesExt + Seek),
     tablename: &str,
+    schemas: &Schemas,
 ) -> Result<Vec<(String, String)>, NxdError> {
-    let row_definition = NXD_COLUMNS
+    let row_definition = schemas
         .get(tablename)
-        .ok_or(NxdError::UnsupportedFormat)?;
+        .ok_or(NxdError::UnsupportedFormat)?
+        .cells();
 
-    let rows = read_nxd_header(reader)?
-        .into_iter()
-        .enumerate()
-        .map(|(row_idx, rowinfo)| {
-            read_row(reader, &row_definition, &rowinfo)
-                .map_err(|err| NxdError::RowContext {
-                    row: row_idx,
-                    source: Box::new(err),
-                })
+    let (_, _, rowinfos) = read_nxd_header(reader)?;
+    let rows = read_all_rows(reader, &row_definition, &rowinfos)?;
+    let rows = rowinfos
+        .iter()
+        .zip(&rows)
+        .flat_map(|(rowinfo, cells)| {
+            row_text_entries(tablename, rowinfo.row_key1, rowinfo.row_key2, cells)
         })
-        .collect::<Result<Vec<_>, _>>()?
-        .into_iter()
+        .collect::<Vec<_>>();
+
+    Ok(rows)
+}
+
+
+/// Maps the legacy, row index based translation keys of a table to the current ones.
+pub fn read_key_migration(
+    reader: &mut (impl ReadBytesExt + Seek),
+    tablename: &str,
+    schemas: &Schemas,
+) -> Result<Vec<(String, String)>, NxdError> {
+    let row_definition = schemas
+        .get(tablename)
+        .ok_or(NxdError::UnsupportedFormat)?
+        .cells();
+
+    let (rowinfos, rows) = read_text_rows(reader, &row_definition)?;
+    let keys = rowinfos
+        .iter()
+        .zip(rows)
         .enumerate()
-        .flat_map(|(row_idx, row)| {
-            row.into_iter().map(move |(cell_idx, text)| {
-                let key = create_translation_key(tablename, row_idx, cell_idx);
-                (key, text)
+        .flat_map(|(row_idx, (rowinfo, row))| {
+            row.into_iter().map(move |(cell_idx, _)| {
+                let old_key = create_legacy_translation_key(tablename, row_idx, cell_idx);
+                let new_key = create_translation_key(tablename, rowinfo.row_key1, rowinfo.row_key2, cell_idx);
+                (old_key, new_key)
             })
         })
         .collect::<Vec<_>>();
 
-    Ok(rows)
+    Ok(keys)
 }
 
 
-pub fn update_rows(
+/// Fails if the text overrides of the table look like they use the legacy keys. Those can
+/// match existing rows by chance, which would put the texts into the wrong rows. Keys that
+/// only match as legacy ones must be outnumbered by the ones that only match as current
+/// ones, and keys that match a different row both ways need at least one current-only key
+/// besides them. Keys that match nothing are left to the import report.
+pub fn check_legacy_keys(
     reader: &mut (impl ReadBytesExt + Seek),
     tablename: &str,
+    schemas: &Schemas,
     text_overrides: &HashMap<String, String>,
-) -> Result<Vec<u8>, NxdError> {
-    let row_definition = NXD_COLUMNS
+) -> Result<(), NxdError> {
+    let migration = read_key_migration(reader, tablename, schemas)?;
+    let legacy_keys = migration
+        .iter()
+        .map(|(old_key, new_key)| (old_key, new_key))
+        .collect::<HashMap<_, _>>();
+    let current_keys = migration.iter().map(|(_, new_key)| new_key).collect::<HashSet<_>>();
+
+    let (mut legacy_only, mut current_only, mut ambiguous) = (0, 0, 0);
+    let table_keys = text_overrides
+        .keys()
+        .filter(|key| key.split('/').next() == Some(tablename));
+    for key in table_keys {
+        match (legacy_keys.get(key), current_keys.contains(key)) {
+            // The same cell either way
+            (Some(new_key), _) if *new_key == key => current_only += 1,
+            (Some(_), true) => ambiguous += 1,
+            (Some(_), false) => legacy_only += 1,
+            (None, true) => current_only += 1,
+            (None, false) => (),
+        }
+    }
+
+    if legacy_only > 0 && legacy_only >= current_only {
+        return Err(NxdError::LegacyKeys);
+    }
+    match ambiguous > 0 && current_only == 0 {
+        true => Err(NxdError::AmbiguousKeys),
+        false => Ok(()),
+    }
+}
+
+
+/// Applies text overrides to a table read with `read_table`. With `add_missing_rows`,
+/// keys of text cells in rows that don't exist yet create new, blank rows.
+pub fn update_table(
+    table: &mut NxdTable,
+    tablename: &str,
+    text_overrides: &HashMap<String, String>,
+    add_missing_rows: bool,
+) -> Result<ImportReport, NxdError> {
+    if add_missing_rows {
+        let mut new_rows = text_overrides
+            .keys()
+            .filter_map(|key| parse_translation_key(tablename, key))
+            .filter(|(_, row_key2, cell_idx)| {
+                row_key2.is_some() == table.is_double_key()
+                    && matches!(table.columns.get(*cell_idx), Some(Cell::Str(_)))
+            })
+            .map(|(row_key1, row_key2, _)| (row_key1, row_key2))
+            .collect::<Vec<_>>();
+        new_rows.sort();
+        new_rows.dedup();
+
+        for (row_key1, row_key2) in new_rows {
+            if table.find_row(row_key1, row_key2).is_none() {
+                table.insert_row(table.blank_row(row_key1, row_key2))?;
+            }
+        }
+    }
+
+    for row in table.rows.iter_mut() {
+        for (cell_idx, cell) in row.cells.iter_mut().enumerate() {
+            match cell {
+                CellValue::Str(text) => {
+                    let key = create_translation_key(tablename, row.key1, row.key2, cell_idx);
+                    if let Some(new_text) = text_overrides.get(&key) {
+                        text.clone_from(new_text);
+                    }
+                },
+                CellValue::StrArray(texts) => {
+                    for (elem_idx, text) in texts.iter_mut().enumerate() {
+                        let key = create_element_key(tablename, row.key1, row.key2, cell_idx, elem_idx);
+                        if let Some(new_text) = text_overrides.get(&key) {
+                            text.clone_from(new_text);
+                        }
+                    }
+                },
+                _ => {},
+            }
+        }
+    }
+
+    let table_keys = table
+        .rows
+        .iter()
+        .flat_map(|row| row_text_entries(tablename, row.key1, row.key2, &row.cells))
+        .map(|(key, _)| key)
+        .collect::<Vec<_>>();
+    Ok(ImportReport::new(&table_keys, text_overrides))
+}
+
+
+/// Reads every column that carries data, numbers included, along with their keys.
+pub fn read_values(
+    reader: &mut (impl ReadBytesExt + Seek),
+    tablename: &str,
+    schemas: &Schemas,
+) -> Result<Vec<(String, Value)>, NxdError> {
+    let table = read_table(reader, tablename, schemas)?;
+    let values = table
+        .rows
+        .iter()
+        .flat_map(|row| row_values(tablename, row.key1, row.key2, &row.cells))
+        .collect();
+    Ok(values)
+}
+
+
+/// Writes numbers into the cells of an existing file, leaving every other byte untouched.
+/// Also returns the keys of the overrides that didn't match any cell, sorted.
+pub fn update_values(
+    reader: &mut (impl ReadBytesExt + Seek),
+    tablename: &str,
+    schemas: &Schemas,
+    value_overrides: &HashMap<String, Value>,
+) -> Result<(Vec<u8>, Vec<String>), NxdError> {
+    let row_definition = schemas
         .get(tablename)
-        .ok_or(NxdError::UnsupportedFormat)?;
+        .ok_or(NxdError::UnsupportedFormat)?
+        .cells();
 
-    let (rowinfos, rows, textarea_abs_pos) = {
-        let rowinfos = read_nxd_header(reader)?;
-        let rowinfos_end = reader.stream_position()?;
+    let (_, _, rowinfos) = read_nxd_header(reader)?;
 
-        let rows = rowinfos
+    let mut out_buf = Vec::new();
+    reader.rewind()?;
+    reader.read_to_end(&mut out_buf)?;
+    let file_len = out_buf.len() as u64;
+    let mut out_buf = Cursor::new(out_buf);
+    let cell_offsets = cell_offsets(&row_definition);
+    let mut applied_keys = HashSet::new();
+
+    for rowinfo in &rowinfos {
+        let rowdata_pos = rowinfo.rowdata_pos.abs_target_from(rowinfo.self_pos)?;
+        let (row_key1, row_key2) = (rowinfo.row_key1, rowinfo.row_key2);
+
+        for (cell_idx, column) in row_definition.iter().enumerate() {
+            let cell_abs_pos = rowdata_pos + cell_offsets[cell_idx];
+            if cell_abs_pos + column.size() > file_len {
+                return Err(NxdError::InvalidHeader);
+            }
+
+            // The positions of the numbers this column has, with their keys
+            let targets = match column {
+                Cell::U32Array(relative_field) | Cell::StrArray(relative_field) => {
+                    out_buf.seek(SeekFrom::Start(cell_abs_pos))?;
+                    let (array_pos, count) = read_array_info(&mut out_buf, *relative_field)?;
+                    (0..count as usize)
+                        .map(|elem_idx| {
+                            let key = create_element_key(tablename, row_key1, row_key2, cell_idx, elem_idx);
+                            (key, array_pos + (elem_idx as u64) * 4)
+                        })
+                        .collect()
+                },
+                _ => {
+                    let key = create_translation_key(tablename, row_key1, row_key2, cell_idx);
+                    vec![(key, cell_abs_pos)]
+                },
+            };
+
+            for (key, target_pos) in targets {
+                let value = match value_overrides.get(&key) {
+                    Some(value) => value,
+                    None => continue,
+                };
+                let raw = value
+                    .to_raw(column)
+                    .ok_or_else(|| NxdError::InvalidValue { key: key.clone() })?;
+
+                if target_pos + 4 > file_len {
+                    return Err(NxdError::InvalidHeader);
+                }
+                out_buf.seek(SeekFrom::Start(target_pos))?;
+                write_u32(raw, &mut out_buf)?;
+                applied_keys.insert(key);
+            }
+        }
+    }
+
+    let mut unknown = value_overrides
+        .keys()
+        .filter(|key| !applied_keys.contains(*key))
+        .cloned()
+        .collect::<Vec<_>>();
+    unknown.sort();
+    Ok((out_buf.into_inner(), unknown))
+}
+
+
+/// A text pointer in the file, along with the key of its text. The pointers of `EmptyStr`
+/// cells are not included, as it's unknown what they are relative to. They are kept as they
+/// are, pointing to the empty string at the start of the text area.
+struct TextPtr {
+    pos: u64,
+    base: u64,
+    key: String,
+}
+
+/// The parts of a file the header and the rows refer to.
+struct FileRegions {
+    /// Everything that is not text: the header, the set and row infos, the rows and the arrays
+    data: Vec<Range<u64>>,
+    text_ptrs: Vec<TextPtr>,
+}
+
+fn map_file_regions(
+    reader: &mut (impl ReadBytesExt + Seek),
+    tablename: &str,
+    row_definition: &[Cell],
+) -> Result<FileRegions, NxdError> {
+    let (_, _, rowinfos) = read_nxd_header(reader)?;
+    let mut data = read_header_regions(reader)?;
+    let mut text_ptrs = Vec::new();
+
+    let cell_offsets = cell_offsets(row_definition);
+    let row_size = row_definition.iter().map(Cell::size).sum::<u64>();
+
+    for rowinfo in &rowinfos {
+        let rowdata_pos = rowinfo.rowdata_pos.abs_target_from(rowinfo.self_pos)?;
+        let (row_key1, row_key2) = (rowinfo.row_key1, rowinfo.row_key2);
+        data.push(rowdata_pos..rowdata_pos + row_size);
+
+        for (cell_idx, column) in row_definition.iter().enumerate() {
+            let cell_abs_pos = rowdata_pos + cell_offsets[cell_idx];
+            match column {
+                Cell::Str(relative_field) => text_ptrs.push(TextPtr {
+                    pos: cell_abs_pos,
+                    base: safe_pos_add(cell_abs_pos, (*relative_field as i32) * 4)?,
+                    key: create_translation_key(tablename, row_key1, row_key2, cell_idx),
+                }),
+                Cell::U32Array(relative_field) | Cell::StrArray(relative_field) => {
+                    reader.seek(SeekFrom::Start(cell_abs_pos))?;
+                    let (array_pos, count) = read_array_info(reader, *relative_field)?;
+                    // Empty arrays may point anywhere, even past the end of the file
+                    if count > 0 {
+                        data.push(array_pos..array_pos + (count as u64) * 4);
+                    }
+
+                    if let Cell::StrArray(_) = column {
+                        for elem_idx in 0..count as usize {
+                            let elem_pos = array_pos + (elem_idx as u64) * 4;
+                            let key = create_element_key(tablename, row_key1, row_key2, cell_idx, elem_idx);
+                            text_ptrs.push(TextPtr {
+                                pos: elem_pos,
+                                base: elem_pos,
+                                key,
+                            });
+                        }
+                    }
+                },
+                _ => {},
+            }
+        }
+    }
+
+    Ok(FileRegions { data, text_ptrs })
+}
+
+
+/// Makes sure every byte of the file is either part of a region, or padding.
+fn check_regions_cover(file: &[u8], mut regions: Vec<Range<u64>>) -> Result<(), NxdError> {
+    regions.sort_by_key(|region| region.start);
+
+    let check_gap = |gap: Range<u64>| match file[gap.start as usize..gap.end as usize]
+        .iter()
+        .position(|byte| *byte != 0)
+    {
+        Some(idx) => Err(NxdError::UnmappedData {
+            offset: gap.start + idx as u64,
+        }),
+        None => Ok(()),
+    };
+
+    let mut pos = 0;
+    for region in regions {
+        if region.end > file.len() as u64 {
+            return Err(NxdError::InvalidHeader);
+        }
+        if region.start > pos {
+            check_gap(pos..region.start)?;
+        }
+        pos = pos.max(region.end);
+    }
+    check_gap(pos..file.len() as u64)
+}
+
+
+fn read_whole_file(reader: &mut (impl ReadBytesExt + Seek)) -> Result<Cursor<Vec<u8>>, NxdError> {
+    let mut file = Vec::new();
+    reader.rewind()?;
+    reader.read_to_end(&mut file)?;
+    Ok(Cursor::new(file))
+}
+
+
+/// Reads the original text of every pointer, along with its position.
+fn read_ptr_texts(file: &mut Cursor<Vec<u8>>, text_ptrs: &[TextPtr]) -> Result<Vec<(u64, String)>, NxdError> {
+    text_ptrs
+        .iter()
+        .map(|ptr| {
+            file.seek(SeekFrom::Start(ptr.pos))?;
+            let text_pos = safe_pos_add(ptr.base, read_i32(file)?)?;
+            let text = read_cstr_at(file, text_pos)?;
+            Ok((text_pos, text))
+        })
+        .collect()
+}
+
+
+/// Reads the file along with the original texts of the pointers, and makes sure
+/// nothing else is in there.
+fn read_mapped_file(
+    reader: &mut (impl ReadBytesExt + Seek),
+    regions: &FileRegions,
+) -> Result<(Cursor<Vec<u8>>, Vec<String>), NxdError> {
+    let mut file = read_whole_file(reader)?;
+    let ptr_texts = read_ptr_texts(&mut file, &regions.text_ptrs)?;
+
+    let mut covered = regions.data.clone();
+    for (text_pos, text) in &ptr_texts {
+        covered.push(*text_pos..text_pos + text.len() as u64 + 1);
+    }
+
+    check_regions_cover(file.get_ref(), covered)?;
+    let texts = ptr_texts.into_iter().map(|(_, text)| text).collect();
+    Ok((file, texts))
+}
+
+
+/// Checks that rebuilding the table with `write_table` would not lose any data
+/// the layout doesn't know about.
+pub fn check_regions(
+    reader: &mut (impl ReadBytesExt + Seek),
+    tablename: &str,
+    schemas: &Schemas,
+) -> Result<(), NxdError> {
+    let row_definition = schemas
+        .get(tablename)
+        .ok_or(NxdError::UnsupportedFormat)?
+        .cells();
+
+    let regions = map_file_regions(reader, tablename, &row_definition)?;
+    read_mapped_file(reader, &regions)?;
+    Ok(())
+}
+
+
+/// How `update_rows` places the texts in the file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextLayout {
+    /// Replaces the text area with a new one, where every text is written once.
+    /// Fails if the file has data that the layout of the table doesn't account for.
+    Rebuild,
+    /// Keeps the file as it is, and writes the texts that changed over the original
+    /// ones where they fit. The rest is appended to the end of the file. Without
+    /// changes, the output is identical to the input.
+    Preserve,
+}
+
+
+/// What an import did with the text overrides.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    /// The keys of the overrides that matched a text of the table, in the order of the table
+    pub applied: Vec<String>,
+    /// The keys of the overrides that didn't match any cell of the table, sorted
+    pub unknown: Vec<String>,
+    /// The keys of the texts of the table that had no override
+    pub missing: Vec<String>,
+    /// The keys of the texts that didn't fit in place, and were moved to the end of the file
+    pub moved: Vec<String>,
+}
+
+impl ImportReport {
+    fn new(table_keys: &[String], text_overrides: &HashMap<String, String>) -> Self {
+        let (applied, missing) = table_keys
             .iter()
-            .enumerate()
-            .map(|(row_idx, rowinfo)| {
-                read_row(reader, &row_definition, &rowinfo)
-                    .map_err(|err| NxdError::RowContext {
-                        row: row_idx,
-                        source: Box::new(err),
-                    })
-            })
-            .collect::<Result<Vec<_>, _>>()?;
-        let rows_end = reader.stream_position()?;
+            .cloned()
+            .partition(|key| text_overrides.contains_key(key));
+
+        let known_keys = table_keys.iter().collect::<HashSet<_>>();
+        let mut unknown = text_overrides
+            .keys()
+            .filter(|key| !known_keys.contains(key))
+            .cloned()
+            .collect::<Vec<_>>();
+        unknown.sort();
+
+        ImportReport {
+            applied,
+            unknown,
+            missing,
+            moved: Vec::new(),
+        }
+    }
+}
+
+
+/// Writes the texts into the file, and reports which overrides were applied. Texts are
+/// only moved to the end of the file with `TextLayout::Preserve`.
+pub fn update_rows(
+    reader: &mut (impl ReadBytesExt + Seek),
+    tablename: &str,
+    schemas: &Schemas,
+    text_overrides: &HashMap<String, String>,
+    layout: TextLayout,
+) -> Result<(Vec<u8>, ImportReport), NxdError> {
+    let row_definition = schemas
+        .get(tablename)
+        .ok_or(NxdError::UnsupportedFormat)?
+        .cells();
 
-        let textarea_abs_pos = std::cmp::max(rowinfos_end, rows_end);
-        (rowinfos, rows, textarea_abs_pos)
+    let table_keys = read_rows(reader, tablename, schemas)?
+        .into_iter()
+        .map(|(key, _)| key)
+        .collect::<Vec<_>>();
+    let mut report = ImportReport::new(&table_keys, text_overrides);
+    reader.rewind()?;
+
+    let regions = map_file_regions(reader, tablename, &row_definition)?;
+    let out_buf = match layout {
+        TextLayout::Rebuild => rebuild_text_area(reader, &row_definition, &regions, text_overrides)?,
+        TextLayout::Preserve => {
+            let (out_buf, moved_keys) = patch_changed_texts(reader, &regions, text_overrides)?;
+            report.moved = moved_keys;
+            out_buf
+        },
     };
+    Ok((out_buf, report))
+}
+
 
+/// Replaces the text area of a file, keeping every other part of it as is.
+fn rebuild_text_area(
+    reader: &mut (impl ReadBytesExt + Seek),
+    row_definition: &[Cell],
+    regions: &FileRegions,
+    text_overrides: &HashMap<String, String>,
+) -> Result<Vec<u8>, NxdError> {
+    let (file, original_texts) = read_mapped_file(reader, regions)?;
+
+    // The text goes after the last region that is kept
+    let textarea_abs_pos = regions.data.iter().map(|region| region.end).max().unwrap_or(0);
     let mut out_buf = {
-        let capacity = reader.seek(SeekFrom::End(0))?;
-        Cursor::new(Vec::with_capacity(capacity as _))
+        let mut out_buf = file.into_inner();
+        out_buf.truncate(textarea_abs_pos as usize);
+        Cursor::new(out_buf)
     };
 
-    let mut tmp_buf = vec![0u8; textarea_abs_pos as _];
-    reader.rewind()?;
-    reader.read_exact(&mut tmp_buf)?;
-    out_buf.write_all(&tmp_buf)?;
-
     let mut text_buf = Cursor::new(Vec::<u8>::new());
     let mut text_rel_offsets = HashMap::<String, u64>::new();
 
+    // The original pointers of the `EmptyStr` cells point here
     if row_definition.contains(&Cell::EmptyStr) {
         let text = String::new();
         write_cstr(&text, &mut text_buf)?;
         text_rel_offsets.insert(text, 0);
     }
 
-    for (row_idx, (rowinfo, rowdata)) in rowinfos.iter().zip(rows).enumerate() {
-        let rowdata_pos = rowinfo.rowdata_pos.abs_target_from(rowinfo.self_pos)?;
+    for (ptr, original_text) in regions.text_ptrs.iter().zip(&original_texts) {
+        let text = text_overrides.get(&ptr.key).unwrap_or(original_text);
+        let text_abs_pos = {
+            let text_rel_pos = match text_rel_offsets.get(text) {
+                Some(offset) => *offset,
+                None => {
+                    let pos = text_buf.stream_position()?;
+                    write_cstr(text, &mut text_buf)?;
+                    text_rel_offsets.insert(text.clone(), pos);
+                    pos
+                },
+            };
+            textarea_abs_pos + text_rel_pos
+        };
 
-        for (cell_idx, original_text) in rowdata {
-            let cell_abs_pos = rowdata_pos + (cell_idx as u64) * 4;
-            if cell_abs_pos >= textarea_abs_pos {
-                return Err(NxdError::InvalidHeader);
+        let distance: u32 = text_abs_pos
+            .checked_sub(ptr.base)
+            .and_then(|val| val.try_into().ok())
+            .ok_or(NxdError::InvalidHeader)?;
+        out_buf.seek(SeekFrom::Start(ptr.pos))?;
+        write_u32(distance, &mut out_buf)?;
+    }
+
+    out_buf.seek(SeekFrom::End(0))?;
+    out_buf.write_all(&text_buf.into_inner())?;
+    Ok(out_buf.into_inner())
+}
+
+
+/// Checks whether `text` can be written over the original text at `slot`. It has to fit,
+/// and every text overlapping the slot has to read the same afterwards, except for empty
+/// ones that remain in the zeroed part.
+fn fits_in_place(
+    slot: &Range<u64>,
+    text: &str,
+    ptr_texts: &[(u64, String)],
+    final_texts: &[&str],
+) -> bool {
+    let text_end = slot.start + text.len() as u64;
+    if text_end >= slot.end {
+        return false;
+    }
+
+    ptr_texts.iter().zip(final_texts).all(|((text_pos, original_text), final_text)| {
+        let span_end = text_pos + original_text.len() as u64 + 1;
+        if span_end <= slot.start || *text_pos >= slot.end {
+            true
+        } else if *text_pos == slot.start {
+            *final_text == text
+        } else {
+            *text_pos >= text_end && final_text.is_empty()
+        }
+    })
+}
+
+
+/// Writes the texts that changed over the original ones where possible, and appends
+/// the rest to the end of the file, clearing their old place. Every other byte stays
+/// the same. Returns the keys of the texts that had to be moved.
+fn patch_changed_texts(
+    reader: &mut (impl ReadBytesExt + Seek),
+    regions: &FileRegions,
+    text_overrides: &HashMap<String, String>,
+) -> Result<(Vec<u8>, Vec<String>), NxdError> {
+    let mut out_buf = read_whole_file(reader)?;
+    let ptr_texts = read_ptr_texts(&mut out_buf, &regions.text_ptrs)?;
+    let final_texts = regions
+        .text_ptrs
+        .iter()
+        .zip(&ptr_texts)
+        .map(|(ptr, (_, original_text))| text_overrides.get(&ptr.key).unwrap_or(original_text).as_str())
+        .collect::<Vec<_>>();
+
+    // Pointers to the same text can only be patched together
+    let mut changed_slots = BTreeMap::<u64, Vec<usize>>::new();
+    for (ptr_idx, (text_pos, original_text)) in ptr_texts.iter().enumerate() {
+        if final_texts[ptr_idx] != original_text {
+            changed_slots.entry(*text_pos).or_default().push(ptr_idx);
+        }
+    }
+
+    let mut moved_ptrs = Vec::new();
+    for (text_pos, ptr_idxs) in changed_slots {
+        let text = final_texts[ptr_idxs[0]];
+        let slot = text_pos..text_pos + ptr_texts[ptr_idxs[0]].1.len() as u64 + 1;
+        if fits_in_place(&slot, text, &ptr_texts, &final_texts) {
+            out_buf.seek(SeekFrom::Start(slot.start))?;
+            out_buf.write_all(text.as_bytes())?;
+            out_buf.write_all(&vec![0; (slot.end - slot.start) as usize - text.len()])?;
+        } else {
+            moved_ptrs.extend(ptr_idxs);
+        }
+    }
+    moved_ptrs.sort_unstable();
+
+    // The moved texts leave their slots behind, which are cleared where no other text uses them
+    let live_spans = ptr_texts
+        .iter()
+        .enumerate()
+        .filter(|(ptr_idx, _)| moved_ptrs.binary_search(ptr_idx).is_err())
+        .map(|(ptr_idx, (text_pos, _))| *text_pos..text_pos + final_texts[ptr_idx].len() as u64 + 1)
+        .collect::<Vec<_>>();
+    for &ptr_idx in &moved_ptrs {
+        let (text_pos, original_text) = &ptr_texts[ptr_idx];
+        for pos in *text_pos..text_pos + original_text.len() as u64 + 1 {
+            if !live_spans.iter().any(|span| span.contains(&pos)) {
+                out_buf.get_mut()[pos as usize] = 0;
             }
-            out_buf.seek(SeekFrom::Start(cell_abs_pos))?;
-
-            let key = create_translation_key(tablename, row_idx, cell_idx);
-            let text = text_overrides.get(&key).unwrap_or(&original_text);
-            let text_abs_pos = {
-                let text_rel_pos = match text_rel_offsets.get(text) {
-                    Some(offset) => *offset,
-                    None => {
-                        let pos = text_buf.stream_position()?;
-                        write_cstr(&text, &mut text_buf)?;
-                        text_rel_offsets.insert(text.clone(), pos);
-                        pos
-                    },
-                };
-                textarea_abs_pos + text_rel_pos
-            };
+        }
+    }
 
-            let ptr_base = {
-                let relative_field = match row_definition[cell_idx] {
-                    Cell::Str(shift) => shift,
-                    _ => return Err(NxdError::InvalidHeader),
-                };
-                safe_pos_add(out_buf.stream_position()?, (relative_field as i32) * 4)?
+    let textarea_abs_pos = out_buf.seek(SeekFrom::End(0))?;
+    let mut text_buf = Cursor::new(Vec::<u8>::new());
+    let mut text_rel_offsets = HashMap::<&str, u64>::new();
+
+    for &ptr_idx in &moved_ptrs {
+        let (ptr, text) = (&regions.text_ptrs[ptr_idx], final_texts[ptr_idx]);
+        let text_abs_pos = {
+            let text_rel_pos = match text_rel_offsets.get(text) {
+                Some(offset) => *offset,
+                None => {
+                    let pos = text_buf.stream_position()?;
+                    write_cstr(text, &mut text_buf)?;
+                    text_rel_offsets.insert(text, pos);
+                    pos
+                },
             };
+            textarea_abs_pos + text_rel_pos
+        };
 
-            let distance: u32 = text_abs_pos
-                .checked_sub(ptr_base)
-                .and_then(|val| val.try_into().ok())
-                .ok_or(NxdError::InvalidHeader)?;
-            write_u32(distance, &mut out_buf)?;
-        }
+        let distance: u32 = text_abs_pos
+            .checked_sub(ptr.base)
+            .and_then(|val| val.try_into().ok())
+            .ok_or(NxdError::InvalidHeader)?;
+        out_buf.seek(SeekFrom::Start(ptr.pos))?;
+        write_u32(distance, &mut out_buf)?;
     }
 
     out_buf.seek(SeekFrom::End(0))?;
     out_buf.write_all(&text_buf.into_inner())?;
-    Ok(out_buf.into_inner())
+
+    let moved_keys = moved_ptrs
+        .iter()
+        .map(|ptr_idx| regions.text_ptrs[*ptr_idx].key.clone())
+        .collect();
+    Ok((out_buf.into_inner(), moved_keys))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nxd_writer::{
+        tests::{sample_schemas, sample_table, TABLENAME},
+        write_table,
+    };
+
+    fn import(
+        data: &[u8],
+        text_overrides: &HashMap<String, String>,
+        layout: TextLayout,
+    ) -> (Vec<u8>, ImportReport) {
+        update_rows(&mut Cursor::new(data), TABLENAME, &sample_schemas(), text_overrides, layout).unwrap()
+    }
+
+    #[test]
+    fn preserve_without_changes() {
+        for is_double_key in [false, true] {
+            let data = write_table(&sample_table(is_double_key)).unwrap();
+            let (out_buf, report) = import(&data, &HashMap::new(), TextLayout::Preserve);
+            assert_eq!(out_buf, data);
+            assert!(report.moved.is_empty());
+        }
+    }
+
+    #[test]
+    fn preserve_with_original_texts() {
+        let data = write_table(&sample_table(false)).unwrap();
+        let text_overrides = read_rows(&mut Cursor::new(&data), TABLENAME, &sample_schemas())
+            .unwrap()
+            .into_iter()
+            .collect();
+        let (out_buf, report) = import(&data, &text_overrides, TextLayout::Preserve);
+        assert_eq!(out_buf, data);
+        assert_eq!(report.applied.len(), text_overrides.len());
+    }
+
+    #[test]
+    fn preserve_in_place() {
+        let data = write_table(&sample_table(false)).unwrap();
+        let text_overrides = HashMap::from([(format!("{}/11/2", TABLENAME), "Delta".to_owned())]);
+        let (out_buf, report) = import(&data, &text_overrides, TextLayout::Preserve);
+
+        assert_eq!(out_buf.len(), data.len());
+        assert!(report.moved.is_empty());
+        let changed_bytes = out_buf.iter().zip(&data).filter(|(new, old)| new != old).count();
+        assert_eq!(changed_bytes, 3);
+    }
+
+    #[test]
+    fn rebuild_after_preserve() {
+        let data = write_table(&sample_table(false)).unwrap();
+        let text_overrides = HashMap::from([
+            (format!("{}/10/2", TABLENAME), "Ramza Beoulve".to_owned()),
+            (format!("{}/11/2", TABLENAME), "Delita Heiral".to_owned()),
+        ]);
+        let (preserved, report) = import(&data, &text_overrides, TextLayout::Preserve);
+        assert_eq!(report.moved.len(), 2);
+
+        check_regions(&mut Cursor::new(&preserved), TABLENAME, &sample_schemas()).unwrap();
+        let (rebuilt, _) = import(&preserved, &HashMap::new(), TextLayout::Rebuild);
+        let texts = read_rows(&mut Cursor::new(&rebuilt), TABLENAME, &sample_schemas()).unwrap();
+        let text = |key: &str| texts.iter().find(|(other, _)| other == key).map(|(_, text)| text.as_str());
+        assert_eq!(text("testtable/10/2"), Some("Ramza Beoulve"));
+        assert_eq!(text("testtable/11/2"), Some("Delita Heiral"));
+        assert_eq!(text("testtable/15/2"), Some("Ramza"));
+    }
+
+    #[test]
+    fn update_values_with_unknown_keys() {
+        let data = write_table(&sample_table(false)).unwrap();
+        let value_overrides = HashMap::from([
+            (format!("{}/10/4", TABLENAME), Value::F32(2.5)),
+            (format!("{}/11/5[1]", TABLENAME), Value::U32(9)),
+            (format!("{}/11/5[2]", TABLENAME), Value::U32(9)),
+            (format!("{}/12/4", TABLENAME), Value::F32(2.5)),
+        ]);
+        let (out_buf, unknown) =
+            update_values(&mut Cursor::new(&data), TABLENAME, &sample_schemas(), &value_overrides).unwrap();
+        assert_eq!(unknown, ["testtable/11/5[2]", "testtable/12/4"]);
+
+        let values = read_values(&mut Cursor::new(&out_buf), TABLENAME, &sample_schemas()).unwrap();
+        let value = |key: &str| values.iter().find(|(other, _)| other == key).map(|(_, value)| value.clone());
+        assert_eq!(value("testtable/10/4"), Some(Value::F32(2.5)));
+        assert_eq!(value("testtable/11/5[1]"), Some(Value::U32(9)));
+        assert_eq!(value("testtable/15/4"), Some(Value::F32(1.5)));
+    }
+
+    /// Checks the given text keys against a table with the row keys 1, 2 and 3, which
+    /// overlap with the row indices.
+    fn check_keys(keys: &[&str]) -> Result<(), NxdError> {
+        let mut table = sample_table(false);
+        for (row_idx, row) in table.rows.iter_mut().enumerate() {
+            row.key1 = row_idx as u32 + 1;
+        }
+        let data = write_table(&table).unwrap();
+        let text_overrides = keys
+            .iter()
+            .map(|key| (format!("{}/{}", TABLENAME, key), String::new()))
+            .collect::<HashMap<_, _>>();
+        check_legacy_keys(&mut Cursor::new(&data), TABLENAME, &sample_schemas(), &text_overrides)
+    }
+
+    #[test]
+    fn legacy_keys() {
+        assert!(check_keys(&["1/2", "2/2", "3/2"]).is_ok());
+        assert!(check_keys(&["1/2", "3/2", "99/2"]).is_ok());
+        assert!(matches!(check_keys(&["0/2", "1/2", "2/2"]), Err(NxdError::LegacyKeys)));
+        // A tie, and an unknown key that doesn't turn the check off
+        assert!(matches!(check_keys(&["0/2", "3/2"]), Err(NxdError::LegacyKeys)));
+        assert!(matches!(check_keys(&["0/2", "1/2", "99/2"]), Err(NxdError::LegacyKeys)));
+        // Could be either, and the rows would differ
+        assert!(matches!(check_keys(&["1/2", "2/2"]), Err(NxdError::AmbiguousKeys)));
+        assert!(matches!(check_keys(&["1/2", "99/2"]), Err(NxdError::AmbiguousKeys)));
+    }
 }
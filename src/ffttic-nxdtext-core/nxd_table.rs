@@ -0,0 +1,343 @@
+// Copyright (C) 2025  Mátyás Mustoha
+
+use crate::{error::NxdError, nxd_tables::Cell};
+use std::ops::Range;
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NxdRowType {
+    SingleKey = 1,
+    DoubleKey,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NxdLocalizationType {
+    SingleKeyUnlocalized = 1,
+    SingleKeyLocalized,
+    DoubleKeyUnlocalized,
+    DoubleKeyLocalized,
+}
+
+
+/// The fixed fields at the start of an NXD file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NxdHeader {
+    pub row_type: NxdRowType,
+    pub localization: NxdLocalizationType,
+    pub uses_base_rowid: bool,
+    pub base_rowid: u32,
+    /// Four fields of unknown purpose, kept as they are.
+    pub unknown: [u32; 4],
+}
+
+
+/// A group of rows sharing the same first key in a double-key table.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NxdSetInfo {
+    pub key1: u32,
+    /// The index of the first row of the set in `NxdTable::rows`
+    pub first_row: usize,
+    pub row_count: usize,
+}
+
+impl NxdSetInfo {
+    /// The indices of the rows that belong to this set.
+    pub fn rows(&self) -> Range<usize> {
+        self.first_row..(self.first_row + self.row_count)
+    }
+}
+
+
+/// The value of a single cell, typed according to the column definition.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CellValue {
+    Zero32(u32),
+    Bool32(u32),
+    Skip32(u32),
+    U32(u32),
+    I32(i32),
+    F32(f32),
+    EmptyStr,
+    Str(String),
+    U32Array(Vec<u32>),
+    StrArray(Vec<String>),
+}
+
+impl CellValue {
+    /// The raw content of a number cell.
+    pub fn raw(&self) -> Option<u32> {
+        match self {
+            CellValue::Zero32(raw) | CellValue::Bool32(raw) | CellValue::Skip32(raw) | CellValue::U32(raw) => Some(*raw),
+            CellValue::I32(value) => Some(*value as u32),
+            CellValue::F32(value) => Some(value.to_bits()),
+            CellValue::EmptyStr | CellValue::Str(_) => None,
+            CellValue::U32Array(_) | CellValue::StrArray(_) => None,
+        }
+    }
+
+    /// The value of the cell for data exports. Pointers to the empty string
+    /// don't carry any data, so they have no value.
+    pub fn to_value(&self) -> Option<Value> {
+        match self {
+            CellValue::Bool32(0) => Some(Value::Bool(false)),
+            CellValue::Bool32(1) => Some(Value::Bool(true)),
+            CellValue::Zero32(raw) | CellValue::Bool32(raw) | CellValue::Skip32(raw) | CellValue::U32(raw) => {
+                Some(Value::U32(*raw))
+            },
+            CellValue::I32(value) => Some(Value::I32(*value)),
+            CellValue::F32(value) => Some(Value::F32(*value)),
+            CellValue::EmptyStr => None,
+            CellValue::Str(text) => Some(Value::Text(text.clone())),
+            CellValue::U32Array(_) | CellValue::StrArray(_) => None,
+        }
+    }
+
+    /// The values of the elements of an array cell.
+    pub fn to_element_values(&self) -> Vec<Value> {
+        match self {
+            CellValue::U32Array(values) => values.iter().map(|value| Value::U32(*value)).collect(),
+            CellValue::StrArray(texts) => texts.iter().map(|text| Value::Text(text.clone())).collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+
+/// A cell value in a form that is easy to edit by hand.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Text(String),
+    Bool(bool),
+    U32(u32),
+    I32(i32),
+    F32(f32),
+}
+
+impl Value {
+    /// Converts a number to the raw content of a cell of the given type, if it fits.
+    /// Columns of unknown type take any integer. For arrays, this is an element.
+    pub(crate) fn to_raw(&self, column: &Cell) -> Option<u32> {
+        match (column, self) {
+            (Cell::U32Array(_), Value::U32(value)) => Some(*value),
+            (Cell::Bool32, Value::Bool(value)) => Some(*value as u32),
+            (Cell::Zero32 | Cell::Bool32 | Cell::Skip32 | Cell::U32, Value::U32(value)) => Some(*value),
+            (Cell::Zero32 | Cell::Skip32, Value::I32(value)) => Some(*value as u32),
+            (Cell::I32, Value::I32(value)) => Some(*value as u32),
+            (Cell::I32, Value::U32(value)) => i32::try_from(*value).ok().map(|value| value as u32),
+            (Cell::F32, Value::F32(value)) => Some(value.to_bits()),
+            (Cell::F32, Value::U32(value)) => Some((*value as f32).to_bits()),
+            (Cell::F32, Value::I32(value)) => Some((*value as f32).to_bits()),
+            _ => None,
+        }
+    }
+}
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NxdRow {
+    pub key1: u32,
+    /// Only present in double-key tables
+    pub key2: Option<u32>,
+    pub cells: Vec<CellValue>,
+}
+
+
+/// The full contents of an NXD file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NxdTable {
+    pub header: NxdHeader,
+    pub columns: Vec<Cell>,
+    /// Only present in double-key tables
+    pub set_infos: Vec<NxdSetInfo>,
+    pub rows: Vec<NxdRow>,
+}
+
+
+impl NxdTable {
+    pub fn is_double_key(&self) -> bool {
+        self.header.row_type == NxdRowType::DoubleKey
+    }
+
+    /// The rows of the set that belongs to the given first key.
+    pub fn set_rows(&self, key1: u32) -> Option<&[NxdRow]> {
+        self.set_infos
+            .iter()
+            .find(|setinfo| setinfo.key1 == key1)
+            .map(|setinfo| &self.rows[setinfo.rows()])
+    }
+
+    /// Makes sure every set points to existing rows, all having the key of the set.
+    pub(crate) fn check_set_infos(&self) -> Result<(), NxdError> {
+        if !self.is_double_key() && !self.set_infos.is_empty() {
+            return Err(NxdError::InvalidSetInfo { set: 0 });
+        }
+
+        for (set_idx, setinfo) in self.set_infos.iter().enumerate() {
+            let is_valid = setinfo
+                .first_row
+                .checked_add(setinfo.row_count)
+                .and_then(|end| self.rows.get(setinfo.first_row..end))
+                .is_some_and(|rows| rows.iter().all(|row| row.key1 == setinfo.key1));
+            if !is_valid {
+                return Err(NxdError::InvalidSetInfo { set: set_idx });
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the index of the row with the given key(s).
+    pub fn find_row(&self, key1: u32, key2: Option<u32>) -> Option<usize> {
+        self.rows
+            .iter()
+            .position(|row| row.key1 == key1 && row.key2 == key2)
+    }
+
+    /// Creates a row that fits the columns of this table, with every number set
+    /// to zero and every text left empty.
+    pub fn blank_row(&self, key1: u32, key2: Option<u32>) -> NxdRow {
+        let cells = self
+            .columns
+            .iter()
+            .map(|column| match column {
+                Cell::Zero32 => CellValue::Zero32(0),
+                Cell::Bool32 => CellValue::Bool32(0),
+                Cell::Skip32 => CellValue::Skip32(0),
+                Cell::U32 => CellValue::U32(0),
+                Cell::I32 => CellValue::I32(0),
+                Cell::F32 => CellValue::F32(0.0),
+                Cell::EmptyStr => CellValue::EmptyStr,
+                Cell::Str(_) => CellValue::Str(String::new()),
+                Cell::U32Array(_) => CellValue::U32Array(Vec::new()),
+                Cell::StrArray(_) => CellValue::StrArray(Vec::new()),
+            })
+            .collect();
+        NxdRow { key1, key2, cells }
+    }
+
+    /// Inserts a row, keeping the rows ordered by their keys. Returns the index
+    /// of the new row.
+    pub fn insert_row(&mut self, row: NxdRow) -> Result<usize, NxdError> {
+        let row_idx = self
+            .rows
+            .iter()
+            .position(|other| (other.key1, other.key2) > (row.key1, row.key2))
+            .unwrap_or(self.rows.len());
+
+        if row.key2.is_some() != self.is_double_key() {
+            return Err(NxdError::InvalidRowKey { row: row_idx });
+        }
+        if row.cells.len() != self.columns.len() {
+            let col = row.cells.len().min(self.columns.len());
+            return Err(NxdError::InvalidCell { row: row_idx, col });
+        }
+        if self.find_row(row.key1, row.key2).is_some() {
+            return Err(NxdError::DuplicateRowKey {
+                key1: row.key1,
+                key2: row.key2,
+            });
+        }
+
+        self.rows.insert(row_idx, row);
+        self.rebuild_set_infos();
+        Ok(row_idx)
+    }
+
+    pub fn remove_row(&mut self, key1: u32, key2: Option<u32>) -> Option<NxdRow> {
+        let row_idx = self.find_row(key1, key2)?;
+        let row = self.rows.remove(row_idx);
+        self.rebuild_set_infos();
+        Some(row)
+    }
+
+    /// Regroups the rows of a double-key table into sets by their first key.
+    fn rebuild_set_infos(&mut self) {
+        if !self.is_double_key() {
+            return;
+        }
+
+        self.set_infos.clear();
+        for (row_idx, row) in self.rows.iter().enumerate() {
+            match self.set_infos.last_mut() {
+                Some(setinfo) if setinfo.key1 == row.key1 => setinfo.row_count += 1,
+                _ => self.set_infos.push(NxdSetInfo {
+                    key1: row.key1,
+                    first_row: row_idx,
+                    row_count: 1,
+                }),
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        nxd::read_table,
+        nxd_writer::{
+            tests::{sample_schemas, sample_table, TABLENAME},
+            write_table,
+        },
+    };
+    use std::io::Cursor;
+
+    fn set_infos(table: &NxdTable) -> Vec<(u32, usize, usize)> {
+        table
+            .set_infos
+            .iter()
+            .map(|setinfo| (setinfo.key1, setinfo.first_row, setinfo.row_count))
+            .collect()
+    }
+
+    fn read_back(table: &NxdTable) -> NxdTable {
+        let data = write_table(table).unwrap();
+        read_table(&mut Cursor::new(data), TABLENAME, &sample_schemas()).unwrap()
+    }
+
+    #[test]
+    fn insert_rows_into_sets() {
+        let mut table = sample_table(true);
+
+        assert_eq!(table.insert_row(table.blank_row(10, Some(3))).unwrap(), 2);
+        assert_eq!(set_infos(&table), [(10, 0, 3), (20, 3, 1)]);
+
+        assert_eq!(table.insert_row(table.blank_row(15, Some(1))).unwrap(), 3);
+        assert_eq!(set_infos(&table), [(10, 0, 3), (15, 3, 1), (20, 4, 1)]);
+
+        let read_back = read_back(&table);
+        assert_eq!(set_infos(&read_back), set_infos(&table));
+        assert_eq!(read_back, table);
+    }
+
+    #[test]
+    fn remove_rows_from_sets() {
+        let mut table = sample_table(true);
+
+        assert!(table.remove_row(10, Some(1)).is_some());
+        assert_eq!(set_infos(&table), [(10, 0, 1), (20, 1, 1)]);
+
+        assert!(table.remove_row(20, Some(1)).is_some());
+        assert!(table.remove_row(20, Some(1)).is_none());
+        assert_eq!(set_infos(&table), [(10, 0, 1)]);
+
+        let read_back = read_back(&table);
+        assert_eq!(set_infos(&read_back), set_infos(&table));
+        assert_eq!(read_back, table);
+    }
+
+    #[test]
+    fn insert_and_remove_single_key_rows() {
+        let mut table = sample_table(false);
+
+        assert_eq!(table.insert_row(table.blank_row(12, None)).unwrap(), 2);
+        assert!(table.insert_row(table.blank_row(12, None)).is_err());
+        assert!(table.insert_row(table.blank_row(13, Some(1))).is_err());
+        assert!(table.remove_row(10, None).is_some());
+
+        let keys = table.rows.iter().map(|row| row.key1).collect::<Vec<_>>();
+        assert_eq!(keys, [11, 12, 15]);
+        assert!(table.set_infos.is_empty());
+        assert_eq!(read_back(&table), table);
+    }
+}
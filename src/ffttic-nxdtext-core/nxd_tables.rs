@@ -2,13 +2,31 @@
 
 use phf::phf_map;
 
-#[derive(Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Cell {
     Zero32,
     Bool32,
     Skip32,
+    U32,
+    I32,
+    F32,
     EmptyStr,
     Str(i8),
+    /// An offset and a count, pointing to an array of 32-bit numbers. Like with
+    /// `Str`, the offset is relative to the cell shifted by the given amount.
+    U32Array(i8),
+    /// Same as `U32Array`, but the elements are text pointers relative to themselves.
+    StrArray(i8),
+}
+
+impl Cell {
+    /// The number of bytes the cell takes in the row.
+    pub fn size(&self) -> u64 {
+        match self {
+            Cell::U32Array(_) | Cell::StrArray(_) => 8,
+            _ => 4,
+        }
+    }
 }
 
 pub const NXD_COLUMNS: phf::Map<&'static str, &[Cell]> = phf_map! {
@@ -0,0 +1,354 @@
+// Copyright (C) 2025  Mátyás Mustoha
+
+use crate::{
+    binary::*,
+    error::NxdError,
+    nxd::{NXD_FORMAT, NXD_MAGIC, ROWINFO_1KEY_SIZE, ROWINFO_2KEY_SIZE, SETINFO_SIZE},
+    nxd_table::*,
+    nxd_tables::Cell,
+};
+use byteorder::WriteBytesExt;
+use std::{
+    collections::HashMap,
+    io::{Cursor, Seek, SeekFrom, Write},
+};
+
+
+const HEADER_SIZE: u64 = 0x20;
+const KEY1_HEADER_SIZE: u64 = HEADER_SIZE + 2 * 4;
+const KEY2_HEADER_SIZE: u64 = HEADER_SIZE + 5 * 4;
+
+
+fn rel_offset(base: u64, target: u64) -> Result<i32, NxdError> {
+    (target as i64 - base as i64)
+        .try_into()
+        .map_err(|_| NxdError::InvalidHeader)
+}
+
+fn abs_u32(pos: u64) -> Result<u32, NxdError> {
+    pos.try_into().map_err(|_| NxdError::InvalidHeader)
+}
+
+
+fn validate_table(table: &NxdTable) -> Result<(), NxdError> {
+    let is_double_key = match (table.header.row_type, table.header.localization) {
+        (NxdRowType::SingleKey, NxdLocalizationType::SingleKeyUnlocalized) => false,
+        (NxdRowType::SingleKey, NxdLocalizationType::SingleKeyLocalized) => false,
+        (NxdRowType::DoubleKey, NxdLocalizationType::DoubleKeyUnlocalized) => true,
+        (NxdRowType::DoubleKey, NxdLocalizationType::DoubleKeyLocalized) => true,
+        _ => return Err(NxdError::InvalidHeader),
+    };
+
+    for (row_idx, row) in table.rows.iter().enumerate() {
+        if row.key2.is_some() != is_double_key {
+            return Err(NxdError::InvalidRowKey { row: row_idx });
+        }
+        if row.cells.len() != table.columns.len() {
+            let col = row.cells.len().min(table.columns.len());
+            return Err(NxdError::InvalidCell { row: row_idx, col });
+        }
+        for (col_idx, (cell, column)) in row.cells.iter().zip(&table.columns).enumerate() {
+            let matches = matches!(
+                (cell, column),
+                (CellValue::Zero32(_), Cell::Zero32)
+                    | (CellValue::Bool32(_), Cell::Bool32)
+                    | (CellValue::Skip32(_), Cell::Skip32)
+                    | (CellValue::U32(_), Cell::U32)
+                    | (CellValue::I32(_), Cell::I32)
+                    | (CellValue::F32(_), Cell::F32)
+                    | (CellValue::EmptyStr, Cell::EmptyStr)
+                    | (CellValue::Str(_), Cell::Str(_))
+                    | (CellValue::U32Array(_), Cell::U32Array(_))
+                    | (CellValue::StrArray(_), Cell::StrArray(_))
+            );
+            if !matches {
+                return Err(NxdError::InvalidCell { row: row_idx, col: col_idx });
+            }
+        }
+    }
+
+    table.check_set_infos()
+}
+
+
+/// Collects the text area in the order the strings are first used. The empty
+/// string, when needed, always goes first.
+fn build_text_area(table: &NxdTable) -> Result<(Vec<u8>, HashMap<&str, u64>), NxdError> {
+    let mut text_buf = Cursor::new(Vec::<u8>::new());
+    let mut text_rel_offsets = HashMap::<&str, u64>::new();
+
+    if table.columns.contains(&Cell::EmptyStr) {
+        write_cstr("", &mut text_buf)?;
+        text_rel_offsets.insert("", 0);
+    }
+
+    let texts = table
+        .rows
+        .iter()
+        .flat_map(|row| &row.cells)
+        .flat_map(|cell| match cell {
+            CellValue::Str(text) => std::slice::from_ref(text),
+            CellValue::StrArray(texts) => texts.as_slice(),
+            _ => &[],
+        })
+        .map(String::as_str);
+    for text in texts {
+        if !text_rel_offsets.contains_key(text) {
+            let pos = text_buf.stream_position()?;
+            write_cstr(text, &mut text_buf)?;
+            text_rel_offsets.insert(text, pos);
+        }
+    }
+
+    Ok((text_buf.into_inner(), text_rel_offsets))
+}
+
+
+fn write_header(table: &NxdTable, writer: &mut impl WriteBytesExt) -> Result<(), NxdError> {
+    write_u32(NXD_MAGIC, writer)?;
+    write_u32(NXD_FORMAT, writer)?;
+    writer.write_u8(table.header.row_type as u8)?;
+    writer.write_u8(table.header.localization as u8)?;
+    writer.write_u8(table.header.uses_base_rowid as u8)?;
+    writer.write_u8(0)?;
+    write_u32(table.header.base_rowid, writer)?;
+    for field in table.header.unknown {
+        write_u32(field, writer)?;
+    }
+    Ok(())
+}
+
+
+/// The elements of the arrays of a row, in order.
+fn array_elements(row: &NxdRow) -> impl Iterator<Item = &CellValue> {
+    row.cells
+        .iter()
+        .filter(|cell| matches!(cell, CellValue::U32Array(_) | CellValue::StrArray(_)))
+}
+
+fn array_len(cell: &CellValue) -> usize {
+    match cell {
+        CellValue::U32Array(values) => values.len(),
+        CellValue::StrArray(texts) => texts.len(),
+        _ => 0,
+    }
+}
+
+
+/// Serializes a complete table. The output is laid out as the header, the set
+/// infos, the row infos, the row data, the array elements and finally the text area.
+pub fn write_table(table: &NxdTable) -> Result<Vec<u8>, NxdError> {
+    validate_table(table)?;
+
+    let is_double_key = table.header.row_type == NxdRowType::DoubleKey;
+    let row_size = table.columns.iter().map(Cell::size).sum::<u64>();
+
+    let setinfo_pos_abs = if is_double_key { KEY2_HEADER_SIZE } else { KEY1_HEADER_SIZE };
+    let rowinfo_pos_abs = setinfo_pos_abs + (table.set_infos.len() as u64) * SETINFO_SIZE;
+    let rowinfo_size = if is_double_key { ROWINFO_2KEY_SIZE } else { ROWINFO_1KEY_SIZE };
+    let rowdata_pos_abs = rowinfo_pos_abs + (table.rows.len() as u64) * rowinfo_size;
+    let arrays_pos_abs = rowdata_pos_abs + (table.rows.len() as u64) * row_size;
+    let arrays_size = table
+        .rows
+        .iter()
+        .flat_map(array_elements)
+        .map(|cell| (array_len(cell) as u64) * 4)
+        .sum::<u64>();
+    let textarea_abs_pos = arrays_pos_abs + arrays_size;
+
+    let (text_area, text_rel_offsets) = build_text_area(table)?;
+
+    let mut out_buf = {
+        let capacity = textarea_abs_pos + text_area.len() as u64;
+        Cursor::new(Vec::with_capacity(capacity as _))
+    };
+
+    write_header(table, &mut out_buf)?;
+    let row_count = abs_u32(table.rows.len() as u64)?;
+    if is_double_key {
+        let self_pos = out_buf.stream_position()?;
+        write_u32(rel_offset(self_pos, setinfo_pos_abs)? as u32, &mut out_buf)?;
+        write_u32(abs_u32(table.set_infos.len() as u64)?, &mut out_buf)?;
+        write_u32(0, &mut out_buf)?;
+        write_u32(abs_u32(rowinfo_pos_abs)?, &mut out_buf)?;
+        write_u32(row_count, &mut out_buf)?;
+    } else {
+        write_u32(abs_u32(rowinfo_pos_abs)?, &mut out_buf)?;
+        write_u32(row_count, &mut out_buf)?;
+    }
+
+    for setinfo in &table.set_infos {
+        let self_pos = out_buf.stream_position()?;
+        let first_rowinfo_pos = rowinfo_pos_abs + (setinfo.first_row as u64) * ROWINFO_2KEY_SIZE;
+        write_u32(setinfo.key1, &mut out_buf)?;
+        write_u32(rel_offset(self_pos, first_rowinfo_pos)? as u32, &mut out_buf)?;
+        write_u32(abs_u32(setinfo.row_count as u64)?, &mut out_buf)?;
+    }
+
+    for (row_idx, row) in table.rows.iter().enumerate() {
+        let self_pos = out_buf.stream_position()?;
+        let row_pos = rowdata_pos_abs + (row_idx as u64) * row_size;
+        write_u32(row.key1, &mut out_buf)?;
+        if let Some(key2) = row.key2 {
+            write_u32(key2, &mut out_buf)?;
+        }
+        write_u32(rel_offset(self_pos, row_pos)? as u32, &mut out_buf)?;
+    }
+
+    let mut next_array_pos = arrays_pos_abs;
+    for row in &table.rows {
+        for (cell, column) in row.cells.iter().zip(&table.columns) {
+            if let Some(raw) = cell.raw() {
+                write_u32(raw, &mut out_buf)?;
+                continue;
+            }
+
+            let (target_abs_pos, shift) = match (cell, column) {
+                (CellValue::Str(text), Cell::Str(shift)) => {
+                    (textarea_abs_pos + text_rel_offsets[text.as_str()], *shift)
+                },
+                (_, Cell::U32Array(shift) | Cell::StrArray(shift)) => (next_array_pos, *shift),
+                _ => (textarea_abs_pos + text_rel_offsets[""], 0),
+            };
+            let ptr_base = out_buf
+                .stream_position()?
+                .checked_add_signed((shift as i64) * 4)
+                .ok_or(NxdError::InvalidHeader)?;
+            write_u32(rel_offset(ptr_base, target_abs_pos)? as u32, &mut out_buf)?;
+
+            if let Cell::U32Array(_) | Cell::StrArray(_) = column {
+                let len = array_len(cell);
+                write_u32(abs_u32(len as u64)?, &mut out_buf)?;
+                next_array_pos += (len as u64) * 4;
+            }
+        }
+    }
+
+    for cell in table.rows.iter().flat_map(array_elements) {
+        match cell {
+            CellValue::U32Array(values) => {
+                for value in values {
+                    write_u32(*value, &mut out_buf)?;
+                }
+            },
+            CellValue::StrArray(texts) => {
+                for text in texts {
+                    let self_pos = out_buf.stream_position()?;
+                    let text_abs_pos = textarea_abs_pos + text_rel_offsets[text.as_str()];
+                    write_u32(rel_offset(self_pos, text_abs_pos)? as u32, &mut out_buf)?;
+                }
+            },
+            _ => {},
+        }
+    }
+
+    out_buf.seek(SeekFrom::End(0))?;
+    out_buf.write_all(&text_area)?;
+    Ok(out_buf.into_inner())
+}
+
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{nxd::read_table, schema::Schemas, TableSchema};
+
+    pub(crate) const TABLENAME: &str = "testtable";
+
+    pub(crate) fn sample_columns() -> Vec<Cell> {
+        vec![
+            Cell::Zero32,
+            Cell::EmptyStr,
+            Cell::Str(0),
+            Cell::Str(-1),
+            Cell::F32,
+            Cell::U32Array(0),
+            Cell::StrArray(0),
+        ]
+    }
+
+    fn sample_row(key1: u32, key2: Option<u32>, name: &str) -> NxdRow {
+        NxdRow {
+            key1,
+            key2,
+            cells: vec![
+                CellValue::Zero32(0),
+                CellValue::EmptyStr,
+                CellValue::Str(name.to_owned()),
+                CellValue::Str(format!("{} description", name)),
+                CellValue::F32(1.5),
+                CellValue::U32Array(vec![key1, 7]),
+                CellValue::StrArray(vec![format!("{} alias", name), "shared".to_owned()]),
+            ],
+        }
+    }
+
+    /// A table with three rows, sharing some of their texts. Double-key tables have
+    /// two sets.
+    pub(crate) fn sample_table(is_double_key: bool) -> NxdTable {
+        let (row_type, localization) = match is_double_key {
+            true => (NxdRowType::DoubleKey, NxdLocalizationType::DoubleKeyLocalized),
+            false => (NxdRowType::SingleKey, NxdLocalizationType::SingleKeyLocalized),
+        };
+        let (rows, set_infos) = match is_double_key {
+            true => (
+                vec![
+                    sample_row(10, Some(1), "Ramza"),
+                    sample_row(10, Some(2), "Delita"),
+                    sample_row(20, Some(1), "Ramza"),
+                ],
+                vec![
+                    NxdSetInfo { key1: 10, first_row: 0, row_count: 2 },
+                    NxdSetInfo { key1: 20, first_row: 2, row_count: 1 },
+                ],
+            ),
+            false => (
+                vec![
+                    sample_row(10, None, "Ramza"),
+                    sample_row(11, None, "Delita"),
+                    sample_row(15, None, "Ramza"),
+                ],
+                Vec::new(),
+            ),
+        };
+
+        NxdTable {
+            header: NxdHeader {
+                row_type,
+                localization,
+                uses_base_rowid: false,
+                base_rowid: 0,
+                unknown: [1, 2, 3, 4],
+            },
+            columns: sample_columns(),
+            set_infos,
+            rows,
+        }
+    }
+
+    pub(crate) fn sample_schemas() -> Schemas {
+        let mut schemas = Schemas::default();
+        schemas.insert(TABLENAME.to_owned(), TableSchema::from_cells(&sample_columns()));
+        schemas
+    }
+
+    #[test]
+    fn round_trip() {
+        for is_double_key in [false, true] {
+            let table = sample_table(is_double_key);
+            let data = write_table(&table).unwrap();
+
+            let read_back = read_table(&mut Cursor::new(&data), TABLENAME, &sample_schemas()).unwrap();
+            assert_eq!(read_back, table);
+            assert_eq!(write_table(&read_back).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn texts_are_written_once() {
+        let data = write_table(&sample_table(false)).unwrap();
+        let count = |text: &[u8]| data.windows(text.len()).filter(|window| *window == text).count();
+        assert_eq!(count(b"Ramza\0"), 1);
+        assert_eq!(count(b"shared\0"), 1);
+    }
+}
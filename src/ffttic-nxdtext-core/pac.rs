@@ -0,0 +1,299 @@
+// Copyright (C) 2025  Mátyás Mustoha
+
+use crate::{binary::*, error::NxdError};
+use byteorder::ReadBytesExt;
+use std::{
+    collections::HashMap,
+    io::{Cursor, Read, Seek, SeekFrom, Write},
+};
+
+
+// The archive layout, as used by FF16Tools: a 0x400 byte header, followed by
+// the entry table. The file names are in a string table somewhere after it.
+// Each entry has the stored size (u32), the compression flags, the file size (u64),
+// the offsets of the name, the data and the compression chunks, a hash of the name,
+// and the CRC-32 of the file.
+pub(crate) const PAC_MAGIC: u32 = u32::from_le_bytes(*b"PACK");
+const PAC_HEADER_SIZE: u64 = 0x400;
+const PAC_ENCRYPTED_POS: u64 = 0x0D;
+const PAC_SIZE_POS: u64 = 0x10;
+const PAC_ENTRY_SIZE: u64 = 0x38;
+const PAC_DATA_ALIGN: u64 = 0x10;
+
+
+/// A file stored in a .pac archive.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PacEntry {
+    pub path: String,
+    /// The size of the file when extracted
+    pub size: u64,
+    /// Compressed entries use Oodle, and can't be extracted here
+    pub is_compressed: bool,
+    /// The position of the entry in the entry table
+    entry_pos: u64,
+    data_offset: u64,
+}
+
+
+/// Lists the files of an archive. Archives with encrypted file names are not supported.
+pub fn read_pac_entries(reader: &mut (impl ReadBytesExt + Seek)) -> Result<Vec<PacEntry>, NxdError> {
+    reader.seek(SeekFrom::Start(0))?;
+    if read_u32(reader)? != PAC_MAGIC {
+        return Err(NxdError::InvalidHeader);
+    }
+    let header_size = read_u32(reader)? as u64;
+    let entry_count = read_u32(reader)?;
+    reader.seek(SeekFrom::Start(PAC_ENCRYPTED_POS))?;
+    if reader.read_u8()? != 0 {
+        return Err(NxdError::PacEncrypted);
+    }
+    if header_size != PAC_HEADER_SIZE {
+        return Err(NxdError::UnsupportedFormat);
+    }
+
+    let mut entries = Vec::with_capacity(entry_count as usize);
+    for entry_idx in 0..entry_count as u64 {
+        let entry_pos = PAC_HEADER_SIZE + entry_idx * PAC_ENTRY_SIZE;
+        reader.seek(SeekFrom::Start(entry_pos + 0x04))?;
+        let is_compressed = reader.read_u8()? != 0;
+        reader.seek(SeekFrom::Start(entry_pos + 0x08))?;
+        let size = read_u64(reader)?;
+        let name_offset = read_u64(reader)?;
+        let data_offset = read_u64(reader)?;
+
+        entries.push(PacEntry {
+            path: read_cstr_at(reader, name_offset)?,
+            size,
+            is_compressed,
+            entry_pos,
+            data_offset,
+        });
+    }
+    Ok(entries)
+}
+
+
+/// Reads the contents of an uncompressed entry.
+pub fn extract_pac_entry(reader: &mut (impl ReadBytesExt + Seek), entry: &PacEntry) -> Result<Vec<u8>, NxdError> {
+    if entry.is_compressed {
+        return Err(NxdError::CompressedPacEntry {
+            path: entry.path.clone(),
+        });
+    }
+
+    reader.seek(SeekFrom::Start(entry.data_offset))?;
+    let mut data = Vec::new();
+    reader.take(entry.size).read_to_end(&mut data)?;
+    if data.len() as u64 != entry.size {
+        return Err(NxdError::InvalidHeader);
+    }
+    Ok(data)
+}
+
+
+/// Replaces the contents of the given entries, looked up by their path. The rest of the
+/// archive is kept as it is, including the compressed entries, and the new contents are
+/// appended uncompressed to the end of it.
+pub fn repack_pac(
+    reader: &mut (impl ReadBytesExt + Seek),
+    replacements: &HashMap<String, Vec<u8>>,
+) -> Result<Vec<u8>, NxdError> {
+    let entries = read_pac_entries(reader)?;
+    if let Some(path) = replacements
+        .keys()
+        .find(|path| !entries.iter().any(|entry| entry.path == **path))
+    {
+        return Err(NxdError::UnknownPacEntry { path: path.clone() });
+    }
+
+    let mut out_buf = Vec::new();
+    reader.seek(SeekFrom::Start(0))?;
+    reader.read_to_end(&mut out_buf)?;
+    let mut writer = Cursor::new(out_buf);
+
+    for entry in entries.iter().filter(|entry| replacements.contains_key(&entry.path)) {
+        let data = &replacements[&entry.path];
+        let size = u32::try_from(data.len()).map_err(|_| NxdError::UnsupportedFormat)?;
+
+        let data_offset = writer.seek(SeekFrom::End(0))?.next_multiple_of(PAC_DATA_ALIGN);
+        writer.get_mut().resize(data_offset as usize, 0);
+        writer.get_mut().extend_from_slice(data);
+
+        // Stored size, no compression, and the file size
+        writer.seek(SeekFrom::Start(entry.entry_pos))?;
+        write_u32(size, &mut writer)?;
+        writer.write_all(&[0, 0])?;
+        writer.seek(SeekFrom::Start(entry.entry_pos + 0x08))?;
+        write_u64(size as u64, &mut writer)?;
+        // After the name, the data offset and no compression chunks. The name hash stays.
+        writer.seek(SeekFrom::Start(entry.entry_pos + 0x18))?;
+        write_u64(data_offset, &mut writer)?;
+        write_u64(0, &mut writer)?;
+        writer.seek(SeekFrom::Start(entry.entry_pos + 0x30))?;
+        write_u32(crc32(data), &mut writer)?;
+    }
+
+    let pack_size = writer.get_ref().len() as u64;
+    writer.seek(SeekFrom::Start(PAC_SIZE_POS))?;
+    write_u64(pack_size, &mut writer)?;
+    Ok(writer.into_inner())
+}
+
+
+/// The usual CRC-32 (IEEE) checksum.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ 0xEDB88320,
+                _ => crc >> 1,
+            };
+        }
+    }
+    !crc
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMPRESSED_SIZE: u64 = 0x40;
+
+    /// Builds an archive with uncompressed entries, plus one marked as compressed.
+    fn sample_pac(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let compressed: (&str, &[u8]) = ("system/compressed.bin", b"oodle");
+        let files = files.iter().chain([&compressed]).collect::<Vec<_>>();
+
+        let names_pos = PAC_HEADER_SIZE + files.len() as u64 * PAC_ENTRY_SIZE;
+        let names = files
+            .iter()
+            .flat_map(|(path, _)| path.bytes().chain([0]))
+            .collect::<Vec<_>>();
+        let contents_pos = names_pos + names.len() as u64;
+
+        let mut header = Cursor::new(vec![0; PAC_HEADER_SIZE as usize]);
+        write_u32(PAC_MAGIC, &mut header).unwrap();
+        write_u32(PAC_HEADER_SIZE as u32, &mut header).unwrap();
+        write_u32(files.len() as u32, &mut header).unwrap();
+
+        let mut entries = Cursor::new(Vec::new());
+        let (mut name_offset, mut data_offset) = (names_pos, contents_pos);
+        for (entry_idx, (path, data)) in files.iter().enumerate() {
+            let is_compressed = entry_idx == files.len() - 1;
+            let (flags, size, chunk_offset) = match is_compressed {
+                true => ([1, 1, 0, 0], COMPRESSED_SIZE, contents_pos),
+                false => ([0; 4], data.len() as u64, 0),
+            };
+            write_u32(data.len() as u32, &mut entries).unwrap();
+            entries.write_all(&flags).unwrap();
+            write_u64(size, &mut entries).unwrap();
+            write_u64(name_offset, &mut entries).unwrap();
+            write_u64(data_offset, &mut entries).unwrap();
+            write_u64(chunk_offset, &mut entries).unwrap();
+            write_u64(0x1234_5678_0000_0000 + entry_idx as u64, &mut entries).unwrap();
+            write_u64(crc32(data) as u64, &mut entries).unwrap();
+            name_offset += path.len() as u64 + 1;
+            data_offset += data.len() as u64;
+        }
+
+        let contents = files.iter().flat_map(|(_, data)| data.iter().copied());
+        [header.into_inner(), entries.into_inner(), names, contents.collect()].concat()
+    }
+
+    fn extract_all(data: &[u8]) -> Vec<(String, Result<Vec<u8>, String>)> {
+        let mut reader = Cursor::new(data);
+        read_pac_entries(&mut reader)
+            .unwrap()
+            .iter()
+            .map(|entry| {
+                let contents = extract_pac_entry(&mut reader, entry).map_err(|err| err.to_string());
+                (entry.path.clone(), contents)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn list_and_extract() {
+        let data = sample_pac(&[("nxd/ability.en.nxd", b"ability"), ("nxd/job.en.nxd", b"job")]);
+
+        let entries = read_pac_entries(&mut Cursor::new(&data)).unwrap();
+        let listing = entries
+            .iter()
+            .map(|entry| (entry.path.as_str(), entry.size, entry.is_compressed))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            listing,
+            [
+                ("nxd/ability.en.nxd", 7, false),
+                ("nxd/job.en.nxd", 3, false),
+                ("system/compressed.bin", COMPRESSED_SIZE, true),
+            ]
+        );
+
+        let extracted = extract_all(&data);
+        assert_eq!(extracted[0].1, Ok(b"ability".to_vec()));
+        assert_eq!(extracted[1].1, Ok(b"job".to_vec()));
+        assert!(extracted[2].1.is_err());
+    }
+
+    #[test]
+    fn repack_replaced_entries() {
+        let data = sample_pac(&[("nxd/ability.en.nxd", b"ability"), ("nxd/job.en.nxd", b"job")]);
+        let replacements = HashMap::from([("nxd/ability.en.nxd".to_owned(), b"new ability".to_vec())]);
+
+        let repacked = repack_pac(&mut Cursor::new(&data), &replacements).unwrap();
+        assert_eq!(repacked[data.len() - 5..data.len()], *b"oodle");
+        assert_eq!(
+            u64::from_le_bytes(repacked[0x10..0x18].try_into().unwrap()),
+            repacked.len() as u64
+        );
+
+        let extracted = extract_all(&repacked);
+        assert_eq!(extracted[0].1, Ok(b"new ability".to_vec()));
+        assert_eq!(extracted[1].1, Ok(b"job".to_vec()));
+        assert!(extracted[2].1.is_err());
+
+        // The compressed entry still points to its original data
+        let entries = read_pac_entries(&mut Cursor::new(&repacked)).unwrap();
+        assert_eq!(entries[2], read_pac_entries(&mut Cursor::new(&data)).unwrap()[2]);
+
+        let unknown = HashMap::from([("nxd/missing.en.nxd".to_owned(), Vec::new())]);
+        assert!(repack_pac(&mut Cursor::new(&data), &unknown).is_err());
+    }
+
+    #[test]
+    fn repack_entry_fields() {
+        let data = sample_pac(&[("nxd/ability.en.nxd", b"ability"), ("nxd/job.en.nxd", b"job")]);
+        let replacements = HashMap::from([("nxd/ability.en.nxd".to_owned(), b"longer ability".to_vec())]);
+        let repacked = repack_pac(&mut Cursor::new(&data), &replacements).unwrap();
+
+        let field = |data: &[u8], pos: usize, len: usize| {
+            let mut bytes = [0; 8];
+            bytes[..len].copy_from_slice(&data[PAC_HEADER_SIZE as usize + pos..][..len]);
+            u64::from_le_bytes(bytes)
+        };
+        let data_offset = (data.len() as u64).next_multiple_of(PAC_DATA_ALIGN);
+        assert_eq!(field(&repacked, 0x00, 4), 14);
+        assert_eq!(field(&repacked, 0x04, 1), 0);
+        assert_eq!(field(&repacked, 0x05, 1), 0);
+        assert_eq!(field(&repacked, 0x08, 8), 14);
+        assert_eq!(field(&repacked, 0x10, 8), field(&data, 0x10, 8));
+        assert_eq!(field(&repacked, 0x18, 8), data_offset);
+        assert_eq!(field(&repacked, 0x20, 8), 0);
+        assert_eq!(field(&repacked, 0x28, 8), field(&data, 0x28, 8));
+        assert_eq!(field(&repacked, 0x30, 4), crc32(b"longer ability") as u64);
+        assert_eq!(repacked[data_offset as usize..], *b"longer ability");
+
+        // The other entries are left alone
+        let entry_range = (PAC_HEADER_SIZE + PAC_ENTRY_SIZE) as usize..(PAC_HEADER_SIZE + 3 * PAC_ENTRY_SIZE) as usize;
+        assert_eq!(repacked[entry_range.clone()], data[entry_range]);
+    }
+
+    #[test]
+    fn checksum() {
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+    }
+}
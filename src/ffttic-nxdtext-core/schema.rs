@@ -0,0 +1,459 @@
+// Copyright (C) 2025  Mátyás Mustoha
+
+use crate::{
+    error::NxdError,
+    nxd_tables::{Cell, NXD_COLUMNS},
+};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::Path,
+};
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Column {
+    pub name: Option<String>,
+    pub cell: Cell,
+}
+
+
+/// The column layout of a table.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TableSchema {
+    pub columns: Vec<Column>,
+}
+
+impl TableSchema {
+    pub fn from_cells(cells: &[Cell]) -> Self {
+        let columns = cells
+            .iter()
+            .map(|cell| Column { name: None, cell: *cell })
+            .collect();
+        Self { columns }
+    }
+
+    pub fn cells(&self) -> Vec<Cell> {
+        self.columns.iter().map(|column| column.cell).collect()
+    }
+
+    pub fn column_name(&self, col: usize) -> Option<&str> {
+        self.columns.get(col).and_then(|column| column.name.as_deref())
+    }
+
+    /// Formats the layout as a table of a TOML schema file.
+    pub fn to_toml(&self, tablename: &str) -> String {
+        let mut out = format!("[{}]\ncolumns = [\n", tablename);
+        for (col, column) in self.columns.iter().enumerate() {
+            let kind = cell_type_name(&column.cell);
+            let entry = match &column.name {
+                Some(name) => {
+                    let name = toml::Value::String(name.clone());
+                    format!("{{ type = \"{}\", name = {} }}", kind, name)
+                },
+                None => format!("\"{}\"", kind),
+            };
+            out += &format!("    {}, # {}\n", entry, col);
+        }
+        out += "]\n";
+        out
+    }
+
+    /// Parses the `add_column|Name|type` lines of an FF16Tools table layout. Only 32-bit
+    /// columns and arrays of them are supported. The layouts don't tell what the string
+    /// and array pointers are relative to, so every one is assumed to point from its own cell.
+    pub fn from_ff16_layout(content: &str) -> Result<Self, String> {
+        let mut columns = Vec::new();
+
+        for (line_idx, line) in content.lines().enumerate() {
+            let line = line.split_once("//").map_or(line, |(code, _)| code).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut parts = line.split('|').map(str::trim);
+            match parts.next() {
+                Some("meta") => continue,
+                Some("add_column") => {},
+                _ => return Err(format!("unknown directive on line {}", line_idx + 1)),
+            }
+            let (name, kind) = match (parts.next(), parts.next()) {
+                (Some(name), Some(kind)) => (name, kind),
+                _ => return Err(format!("incomplete column on line {}", line_idx + 1)),
+            };
+            let cell = match kind {
+                "int" => Cell::I32,
+                "uint" => Cell::U32,
+                "float" => Cell::F32,
+                "string" => Cell::Str(0),
+                "int[]" | "uint[]" => Cell::U32Array(0),
+                "string[]" => Cell::StrArray(0),
+                _ => {
+                    return Err(format!("unsupported column type `{}` on line {}", kind, line_idx + 1));
+                },
+            };
+            columns.push(Column {
+                name: Some(name.to_owned()),
+                cell,
+            });
+        }
+
+        Ok(Self { columns })
+    }
+}
+
+
+/// A set of table layouts, looked up by table name.
+#[derive(Clone, Debug, Default)]
+pub struct Schemas {
+    tables: HashMap<String, TableSchema>,
+}
+
+impl Schemas {
+    /// The layouts compiled into the program.
+    pub fn builtin() -> Self {
+        let tables = NXD_COLUMNS
+            .entries()
+            .map(|(name, cells)| (name.to_string(), TableSchema::from_cells(cells)))
+            .collect();
+        Self { tables }
+    }
+
+    /// Loads the layouts of a TOML, JSON or FF16Tools layout file, or of every such
+    /// file in a directory. Also returns warnings about the files of a directory that
+    /// couldn't be loaded and were skipped, and about layouts that may not fit the game.
+    pub fn load(path: &Path) -> Result<(Self, Vec<String>), NxdError> {
+        let mut schemas = Self::default();
+        let mut warnings = Vec::new();
+
+        if path.is_dir() {
+            let mut paths = fs::read_dir(path)?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<Result<Vec<_>, _>>()?;
+            paths.sort();
+
+            for file_path in paths {
+                let extension = file_path.extension().and_then(|ext| ext.to_str());
+                if !matches!(extension, Some("toml" | "json" | "layout")) {
+                    continue;
+                }
+                match Self::load_file(&file_path, &mut warnings) {
+                    Ok(file_schemas) => schemas.merge(file_schemas),
+                    Err(err) => warnings.push(format!("{}, the file was skipped", err)),
+                }
+            }
+        } else {
+            schemas = Self::load_file(path, &mut warnings)?;
+        }
+
+        Ok((schemas, warnings))
+    }
+
+    fn load_file(path: &Path, warnings: &mut Vec<String>) -> Result<Self, NxdError> {
+        let content = fs::read_to_string(path).map_err(|err| invalid_schema(path, err.to_string()))?;
+        let invalid_schema = |message: String| invalid_schema(path, message);
+
+        let extension = path.extension().and_then(|ext| ext.to_str());
+        let parsed: Result<SchemaFile, String> = match extension {
+            Some("layout") => return Self::load_layout(path, &content, warnings),
+            Some("json") => serde_json::from_str(&content).map_err(|err| err.to_string()),
+            _ => toml::from_str(&content).map_err(|err| err.to_string()),
+        };
+        let file = parsed.map_err(invalid_schema)?;
+
+        let mut tables = HashMap::with_capacity(file.len());
+        for (tablename, table_def) in file {
+            let columns = table_def
+                .columns
+                .into_iter()
+                .map(|column_def| column_def.into_column())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|message| invalid_schema(format!("table `{}`: {}", tablename, message)))?;
+            tables.insert(tablename, TableSchema { columns });
+        }
+        Ok(Self { tables })
+    }
+
+    /// An FF16Tools layout describes a single table, named after the file. As it doesn't
+    /// tell the shifts of the string pointers, a warning is given when the built-in layout
+    /// of the table has different ones.
+    fn load_layout(path: &Path, content: &str, warnings: &mut Vec<String>) -> Result<Self, NxdError> {
+        let invalid_schema = |message: String| invalid_schema(path, message);
+
+        let tablename = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .ok_or_else(|| invalid_schema("the file name is not valid UTF-8".to_owned()))?;
+        let schema = TableSchema::from_ff16_layout(content).map_err(invalid_schema)?;
+
+        if let Some(builtin_cells) = NXD_COLUMNS.get(tablename) {
+            let cells = schema.cells();
+            let differing_cols = builtin_cells
+                .iter()
+                .enumerate()
+                .filter(|(col, cell)| {
+                    let is_pointer = matches!(cell, Cell::Str(_) | Cell::U32Array(_) | Cell::StrArray(_));
+                    is_pointer && cells.get(*col) != Some(cell)
+                })
+                .map(|(col, _)| col.to_string())
+                .collect::<Vec<_>>();
+            if !differing_cols.is_empty() {
+                warnings.push(format!(
+                    "{}: the built-in layout of `{}` has different pointers in column(s) {}, \
+                    which this layout would replace",
+                    path.display(),
+                    tablename,
+                    differing_cols.join(", ")
+                ));
+            }
+        }
+
+        Ok(Self {
+            tables: HashMap::from([(tablename.to_owned(), schema)]),
+        })
+    }
+
+    /// Adds the tables of another set, replacing the ones with the same name.
+    pub fn merge(&mut self, other: Schemas) {
+        self.tables.extend(other.tables);
+    }
+
+    pub fn get(&self, tablename: &str) -> Option<&TableSchema> {
+        self.tables.get(tablename)
+    }
+
+    pub fn insert(&mut self, tablename: String, schema: TableSchema) {
+        self.tables.insert(tablename, schema);
+    }
+
+    pub fn table_names(&self) -> impl Iterator<Item = &str> {
+        self.tables.keys().map(|name| name.as_str())
+    }
+}
+
+
+fn invalid_schema(path: &Path, message: String) -> NxdError {
+    NxdError::InvalidSchema(format!("{}: {}", path.display(), message))
+}
+
+
+type SchemaFile = BTreeMap<String, TableDef>;
+
+#[derive(Deserialize)]
+struct TableDef {
+    columns: Vec<ColumnDef>,
+}
+
+/// A column is either just its type, eg. `"str(-1)"`, or a table with the
+/// type and optionally the pointer shift and the name of the column.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ColumnDef {
+    Short(String),
+    Full {
+        #[serde(rename = "type")]
+        kind: String,
+        shift: Option<i8>,
+        name: Option<String>,
+    },
+}
+
+impl ColumnDef {
+    fn into_column(self) -> Result<Column, String> {
+        match self {
+            ColumnDef::Short(kind) => Ok(Column {
+                name: None,
+                cell: parse_cell(&kind, None)?,
+            }),
+            ColumnDef::Full { kind, shift, name } => Ok(Column {
+                name,
+                cell: parse_cell(&kind, shift)?,
+            }),
+        }
+    }
+}
+
+
+fn cell_type_name(cell: &Cell) -> String {
+    match cell {
+        Cell::Zero32 => "zero32".to_owned(),
+        Cell::Bool32 => "bool32".to_owned(),
+        Cell::Skip32 => "skip32".to_owned(),
+        Cell::U32 => "u32".to_owned(),
+        Cell::I32 => "i32".to_owned(),
+        Cell::F32 => "f32".to_owned(),
+        Cell::EmptyStr => "emptystr".to_owned(),
+        Cell::Str(shift) => format!("str({})", shift),
+        Cell::U32Array(shift) => format!("u32array({})", shift),
+        Cell::StrArray(shift) => format!("strarray({})", shift),
+    }
+}
+
+fn parse_cell(kind: &str, shift: Option<i8>) -> Result<Cell, String> {
+    let kind = kind.trim().to_ascii_lowercase();
+    let invalid_shift = || format!("invalid shift in `{}`", kind);
+
+    let (kind, shift) = match kind.split_once('(') {
+        Some((name, rest)) => {
+            let inner = rest.strip_suffix(')').ok_or_else(invalid_shift)?;
+            (name.trim(), Some(inner.trim().parse::<i8>().map_err(|_| invalid_shift())?))
+        },
+        None => (kind.as_str(), shift),
+    };
+
+    match (kind, shift) {
+        ("zero32", None) => Ok(Cell::Zero32),
+        ("bool32", None) => Ok(Cell::Bool32),
+        ("skip32", None) => Ok(Cell::Skip32),
+        ("u32", None) => Ok(Cell::U32),
+        ("i32", None) => Ok(Cell::I32),
+        ("f32", None) => Ok(Cell::F32),
+        ("emptystr", None) => Ok(Cell::EmptyStr),
+        ("str", shift) => Ok(Cell::Str(shift.unwrap_or(0))),
+        ("u32array", shift) => Ok(Cell::U32Array(shift.unwrap_or(0))),
+        ("strarray", shift) => Ok(Cell::StrArray(shift.unwrap_or(0))),
+        (_, Some(_)) => Err(format!("only strings and arrays can have a shift, not `{}`", kind)),
+        _ => Err(format!("unknown column type `{}`", kind)),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    /// Writes the files into a new directory under the system's temp directory.
+    fn temp_dir(name: &str, files: &[(&str, &str)]) -> std::path::PathBuf {
+        let dir = env::temp_dir().join(format!("nxdtext-schema-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for (file_name, content) in files {
+            fs::write(dir.join(file_name), content).unwrap();
+        }
+        dir
+    }
+
+    fn parse_toml(content: &str) -> Result<Vec<Cell>, String> {
+        let file: SchemaFile = toml::from_str(content).map_err(|err| err.to_string())?;
+        let table_def = file.into_values().next().unwrap();
+        table_def
+            .columns
+            .into_iter()
+            .map(|column_def| column_def.into_column().map(|column| column.cell))
+            .collect()
+    }
+
+    #[test]
+    fn column_spellings() {
+        let cells = parse_toml(
+            r#"
+            [table]
+            columns = [
+                "str(0)",
+                "STR( -1 )",
+                "strarray",
+                "u32",
+                { type = "str", shift = -2, name = "Name" },
+                { type = "u32array(1)" },
+                { type = "Bool32" },
+            ]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            cells,
+            [
+                Cell::Str(0),
+                Cell::Str(-1),
+                Cell::StrArray(0),
+                Cell::U32,
+                Cell::Str(-2),
+                Cell::U32Array(1),
+                Cell::Bool32,
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_columns() {
+        let error = |column: &str| parse_toml(&format!("[table]\ncolumns = [{}]", column)).unwrap_err();
+        assert_eq!(error(r#""text""#), "unknown column type `text`");
+        assert_eq!(error(r#""u32(1)""#), "only strings and arrays can have a shift, not `u32`");
+        assert_eq!(error(r#""str(x)""#), "invalid shift in `str(x)`");
+        assert_eq!(error(r#"{ type = "f32", shift = 1 }"#), "only strings and arrays can have a shift, not `f32`");
+
+        let dir = temp_dir("invalid", &[("broken.toml", "[table]\ncolumns = [\"text\"]")]);
+        let err = Schemas::load(&dir.join("broken.toml")).unwrap_err().to_string();
+        assert!(err.contains("table `table`: unknown column type `text`"), "{}", err);
+        // Broken files of a directory are only reported
+        let (schemas, warnings) = Schemas::load(&dir).unwrap();
+        assert_eq!(schemas.table_names().count(), 0);
+        assert_eq!(warnings.len(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn ff16_layout() {
+        let layout = "\
+            meta|version|1\n\
+            add_column|Id|uint // the row key is not a column\n\
+            add_column|Name|string\n\
+            add_column|Aliases|string[]\n\
+            add_column|Flags|int[]\n\
+            \n\
+            add_column|Scale|float\n";
+        let schema = TableSchema::from_ff16_layout(layout).unwrap();
+        assert_eq!(
+            schema.cells(),
+            [Cell::U32, Cell::Str(0), Cell::StrArray(0), Cell::U32Array(0), Cell::F32]
+        );
+        assert_eq!(schema.column_name(2), Some("Aliases"));
+
+        let err = TableSchema::from_ff16_layout("add_column|Id|short").unwrap_err();
+        assert_eq!(err, "unsupported column type `short` on line 1");
+    }
+
+    #[test]
+    fn user_layouts_replace_builtin_ones() {
+        let dir = temp_dir(
+            "merge",
+            &[
+                ("charaname.toml", "[charaname]\ncolumns = [\"u32\", { type = \"str\", name = \"Name\" }]"),
+                ("newtable.layout", "add_column|Name|string"),
+                ("notes.txt", "not a layout"),
+            ],
+        );
+        let (user_schemas, warnings) = Schemas::load(&dir).unwrap();
+        assert!(warnings.is_empty());
+
+        let mut schemas = Schemas::builtin();
+        schemas.merge(user_schemas);
+        let charaname = schemas.get("charaname").unwrap();
+        assert_eq!(charaname.cells(), [Cell::U32, Cell::Str(0)]);
+        assert_eq!(charaname.column_name(1), Some("Name"));
+        assert_eq!(schemas.get("newtable").unwrap().cells(), [Cell::Str(0)]);
+        assert_eq!(
+            schemas.get("ability").unwrap().cells(),
+            NXD_COLUMNS.get("ability").unwrap().to_vec()
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn toml_round_trip() {
+        let mut schema = TableSchema::from_cells(&[Cell::Zero32, Cell::Str(-1), Cell::StrArray(2), Cell::F32]);
+        schema.columns[1].name = Some("Name \"quoted\"".to_owned());
+
+        let mut file: SchemaFile = toml::from_str(&schema.to_toml("table")).unwrap();
+        let columns = file
+            .remove("table")
+            .unwrap()
+            .columns
+            .into_iter()
+            .map(|column_def| column_def.into_column().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(columns, schema.columns);
+    }
+}